@@ -0,0 +1,124 @@
+// Copyright (C) 2021 RoccoDev
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use hmac::Hmac;
+use rand::{rngs::OsRng, RngCore};
+use sha2::{Digest, Sha256};
+use strum_macros::{Display, EnumString};
+
+const PBKDF2_ROUNDS: u32 = 100_000;
+
+/// Access level of a staff member. Each role can do everything the previous ones can.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, EnumString, Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum Role {
+    /// Sees staff pages, unredacted chat and the real names of nicked players
+    Viewer,
+    /// Searches chat, hides games from public view, triages reports and reads the audit log
+    Moderator,
+    /// Has every permission, including deleting games
+    Admin,
+}
+
+/// Hashes a password with a random salt, as `pbkdf2-sha256$rounds$salt$hash` in hex
+pub fn hash_password(password: &str) -> String {
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    let hash = pbkdf2(password, &salt, PBKDF2_ROUNDS);
+    format!(
+        "pbkdf2-sha256${}${}${}",
+        PBKDF2_ROUNDS,
+        hex::encode(salt),
+        hex::encode(hash)
+    )
+}
+
+/// Checks a password against a hash produced by [`hash_password`]
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    let parts: Vec<&str> = hash.split('$').collect();
+    let (rounds, salt, expected) = match parts.as_slice() {
+        ["pbkdf2-sha256", rounds, salt, expected] => (rounds, salt, expected),
+        _ => return false,
+    };
+    match (rounds.parse(), hex::decode(salt), hex::decode(expected)) {
+        (Ok(rounds), Ok(salt), Ok(expected)) => {
+            constant_time_eq(&pbkdf2(password, &salt, rounds), &expected)
+        }
+        _ => false,
+    }
+}
+
+/// A random token identifying a session, sent in the session cookie
+pub fn session_token() -> String {
+    let mut token = [0u8; 32];
+    OsRng.fill_bytes(&mut token);
+    hex::encode(token)
+}
+
+/// Sessions are stored by the hash of their token, so that they cannot be taken over from
+/// the database
+pub fn token_hash(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+fn pbkdf2(password: &str, salt: &[u8], rounds: u32) -> [u8; 32] {
+    let mut hash = [0u8; 32];
+    pbkdf2::pbkdf2::<Hmac<Sha256>>(password.as_bytes(), salt, rounds, &mut hash);
+    hash
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hash_password, pbkdf2, token_hash, verify_password, Role};
+
+    #[test]
+    fn verify_password_accepts_only_the_hashed_password() {
+        let hash = hash_password("hunter2");
+        assert!(hash.starts_with("pbkdf2-sha256$100000$"));
+        assert!(verify_password("hunter2", &hash));
+        assert!(!verify_password("hunter3", &hash));
+        assert!(!verify_password("hunter2", "hunter2"));
+    }
+
+    #[test]
+    fn verify_password_reads_the_rounds_and_salt_of_the_hash() {
+        let hash = format!(
+            "pbkdf2-sha256$1${}${}",
+            hex::encode(b"salt"),
+            hex::encode(pbkdf2("hunter2", b"salt", 1))
+        );
+        assert!(verify_password("hunter2", &hash));
+        assert!(!verify_password("hunter2", &hash.replace("$1$", "$2$")));
+    }
+
+    #[test]
+    fn token_hash_is_sha256_in_hex() {
+        assert_eq!(
+            token_hash("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn roles_are_ordered_by_permissions() {
+        assert!(Role::Viewer < Role::Moderator && Role::Moderator < Role::Admin);
+        assert_eq!("moderator".parse(), Ok(Role::Moderator));
+        assert_eq!(Role::Admin.to_string(), "admin");
+    }
+}
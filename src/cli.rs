@@ -0,0 +1,298 @@
+// Copyright (C) 2021 RoccoDev
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{
+    auth::{self, Role},
+    db::{AuditEntry, DbHandle, StaffUser},
+    error::{Error, Result},
+    maps::MapManifest,
+    modes::GameMode,
+    privacy::{self, Identity, PlayerQuery},
+    protos::gamelog::GameLog,
+    web::{encode_game_id, game_summary, parse_game_path},
+    webhooks::{self, Webhooks},
+};
+use protobuf::Message;
+use std::{
+    fs,
+    io::{self, BufRead, IsTerminal},
+    path::Path,
+};
+use strum::IntoEnumIterator;
+
+/// Name recorded in the audit log for actions taken from the command line
+const CONSOLE_USER: &str = "console";
+
+const USAGE: &str = "Usage:
+    kig-web                              Start the web server
+    kig-web user list                    List staff members
+    kig-web user add <name> <role>       Add a staff member, reading their password from stdin
+    kig-web user role <name> <role>      Change the role of a staff member
+    kig-web user password <name>         Change the password of a staff member, from stdin
+    kig-web user remove <name>           Remove a staff member and sign them out
+    kig-web player export <player> <dir> Write every log containing a player to a directory
+    kig-web player erase <player> <reason>
+                                         Anonymize a player in every log containing them
+    kig-web webhook test <mode> <id>     Send a game to the webhooks of its mode
+    kig-web webhook log                  List the latest webhook delivery attempts
+
+Roles: viewer, moderator, admin
+Players are given by UUID or name";
+
+/// Runs a command given on the command line, instead of starting the server
+pub async fn run(args: &[String]) -> io::Result<()> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let db = DbHandle::new().await.unwrap();
+    let res = match args.as_slice() {
+        ["user", "list"] => list_users(&db).await,
+        ["user", "add", name, role] => add_user(&db, name, parse_role(role)?).await,
+        ["user", "role", name, role] => set_role(&db, name, parse_role(role)?).await,
+        ["user", "password", name] => set_password(&db, name).await,
+        ["user", "remove", name] => remove_user(&db, name).await,
+        ["player", "export", player, dir] => export_player(&db, player, Path::new(dir)).await,
+        ["player", "erase", player, reason] => erase_player(&db, player, reason).await,
+        ["webhook", "test", mode, id] => test_webhooks(&db, mode, id).await,
+        ["webhook", "log"] => webhook_log(&db).await,
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+    res.map_err(|e| io::Error::other(e.to_string()))
+}
+
+async fn list_users(db: &DbHandle) -> Result<()> {
+    for user in db.staff_users().await? {
+        println!("{}\t{}", user.name, user.role);
+    }
+    Ok(())
+}
+
+async fn add_user(db: &DbHandle, name: &str, role: Role) -> Result<()> {
+    if db.staff_user(name).await?.is_some() {
+        eprintln!("{} already exists", name);
+        std::process::exit(1);
+    }
+    let user = StaffUser {
+        name: name.into(),
+        role,
+        password: auth::hash_password(&read_password()),
+    };
+    db.save_staff_user(&user).await?;
+    println!("Added {} as {}", user.name, user.role);
+    Ok(())
+}
+
+async fn set_role(db: &DbHandle, name: &str, role: Role) -> Result<()> {
+    let mut user = find_user(db, name).await?;
+    user.role = role;
+    db.save_staff_user(&user).await?;
+    println!("{} is now {}", user.name, user.role);
+    Ok(())
+}
+
+async fn set_password(db: &DbHandle, name: &str) -> Result<()> {
+    let mut user = find_user(db, name).await?;
+    user.password = auth::hash_password(&read_password());
+    db.save_staff_user(&user).await?;
+    // Sessions started with the old password are no longer trusted
+    db.delete_staff_sessions(&user.name).await?;
+    println!("Changed the password of {}", user.name);
+    Ok(())
+}
+
+async fn remove_user(db: &DbHandle, name: &str) -> Result<()> {
+    if !db.remove_staff_user(name).await? {
+        eprintln!("{} does not exist", name);
+        std::process::exit(1);
+    }
+    println!("Removed {}", name);
+    Ok(())
+}
+
+async fn find_user(db: &DbHandle, name: &str) -> Result<StaffUser> {
+    match db.staff_user(name).await? {
+        Some(user) => Ok(user),
+        None => {
+            eprintln!("{} does not exist", name);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// A stored log containing a player
+struct PlayerLog {
+    id: Vec<u8>,
+    log: GameLog,
+    identity: Identity,
+}
+
+/// Finds the logs of a mode containing a player. Only the logs that the search index does not
+/// rule out are read.
+async fn find_player_logs(db: &DbHandle, mode: GameMode, player: &str) -> Result<Vec<PlayerLog>> {
+    let query = PlayerQuery::parse(player);
+    Ok(db
+        .player_game_logs(mode.get_database_id(), &query.key())
+        .await?
+        .into_iter()
+        .filter_map(|(id, log, _)| {
+            privacy::find_identity(&log, &query).map(|identity| PlayerLog { id, log, identity })
+        })
+        .collect())
+}
+
+/// Writes each log as protobuf and as text, alongside a list of the games
+async fn export_player(db: &DbHandle, player: &str, dir: &Path) -> Result<()> {
+    let mut games = String::from("mode\tid\tstart\tnames\n");
+    let mut count = 0;
+    let write = |name: String, contents: &[u8]| {
+        fs::write(dir.join(&name), contents).unwrap_or_else(|e| {
+            eprintln!("Could not write {}: {}", name, e);
+            std::process::exit(1);
+        })
+    };
+    fs::create_dir_all(dir).ok();
+    for mode in GameMode::iter() {
+        for log in find_player_logs(db, mode, player).await? {
+            let name = format!("{}_{}", mode.get_database_id(), encode_game_id(&log.id));
+            write(format!("{}.bin", name), &log.log.write_to_bytes()?);
+            write(
+                format!("{}.txt", name),
+                protobuf::text_format::print_to_string(&log.log).as_bytes(),
+            );
+            games += &format!(
+                "{}\t{}\t{}\t{}\n",
+                mode.get_database_id(),
+                encode_game_id(&log.id),
+                log.log.get_game_start(),
+                log.identity.names.join(", ")
+            );
+            count += 1;
+        }
+    }
+    write("games.tsv".into(), games.as_bytes());
+    db.add_audit_entry(&AuditEntry {
+        action: "export".into(),
+        target: format!("player {}", player),
+        reason: format!("{} games", count),
+        details: String::new(),
+        user: CONSOLE_USER.into(),
+        time: DbHandle::now(),
+    })
+    .await?;
+    println!("Exported {} games to {}", count, dir.display());
+    Ok(())
+}
+
+/// Anonymizes a player in every log, recording each rewritten game in the audit log
+async fn erase_player(db: &DbHandle, player: &str, reason: &str) -> Result<()> {
+    let mut count = 0;
+    for mode in GameMode::iter() {
+        let game = mode.get_database_id();
+        for log in find_player_logs(db, mode, player).await? {
+            let (erased, replacements) = privacy::erase_player(mode, &log.log, &log.identity)?;
+            db.replace_game_log_data(game, log.id.clone(), &erased)
+                .await?;
+            db.add_audit_entry(&AuditEntry {
+                action: "erase".into(),
+                target: format!("{}/{}", game, encode_game_id(&log.id)),
+                reason: reason.into(),
+                details: replacements.to_string(),
+                user: CONSOLE_USER.into(),
+                time: DbHandle::now(),
+            })
+            .await?;
+            println!("Erased from {}/{}", game, encode_game_id(&log.id));
+            count += 1;
+        }
+    }
+    // Game lists and leaderboards are cached for 15 minutes, and cards for an hour by the server
+    // and another hour by clients
+    println!(
+        "Erased {} from {} games. Pages are refreshed within 15 minutes, card images within two hours.",
+        player, count
+    );
+    Ok(())
+}
+
+/// Delivers a stored game to the webhooks of its mode, as if it had just finished
+async fn test_webhooks(db: &DbHandle, mode: &str, id: &str) -> Result<()> {
+    let (mode, id) = parse_game_path(mode.into(), id)?;
+    let (log, _) = db
+        .game_log_by_id(mode.get_database_id(), id.clone())
+        .await?
+        .ok_or(Error::NotFound)?;
+    let summary = game_summary(&MapManifest::load(), mode, &id, &log)?;
+    let webhooks = Webhooks::load();
+    let mut hooks = webhooks.for_mode(mode).peekable();
+    if hooks.peek().is_none() {
+        eprintln!("No webhooks are configured for {}", mode.get_database_id());
+        std::process::exit(1);
+    }
+    for hook in hooks {
+        let delivered = webhooks::deliver(db, hook, &summary).await;
+        println!(
+            "{}\t{}",
+            hook.url,
+            if delivered { "delivered" } else { "failed" }
+        );
+    }
+    Ok(())
+}
+
+async fn webhook_log(db: &DbHandle) -> Result<()> {
+    for delivery in db.webhook_deliveries(50).await? {
+        println!(
+            "{}\t{}\t{}/{}\t#{}\t{}\t{}",
+            time::OffsetDateTime::from_unix_timestamp(delivery.time / 1000)
+                .map_or_else(|_| delivery.time.to_string(), |t| t.to_string()),
+            delivery.url,
+            delivery.game,
+            delivery.game_id,
+            delivery.attempt,
+            delivery
+                .status
+                .map_or_else(|| "-".into(), |s| s.to_string()),
+            delivery.error
+        );
+    }
+    Ok(())
+}
+
+fn parse_role(role: &str) -> io::Result<Role> {
+    role.parse().map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("unknown role {}, expected viewer, moderator or admin", role),
+        )
+    })
+}
+
+/// Reads a password, without echoing it when typed in a terminal. A piped password is read from
+/// the first line of stdin.
+fn read_password() -> String {
+    let password = if io::stdin().is_terminal() {
+        rpassword::prompt_password("Password: ").unwrap()
+    } else {
+        let mut password = String::new();
+        io::stdin().lock().read_line(&mut password).unwrap();
+        password.trim_end_matches(['\r', '\n']).to_string()
+    };
+    if password.is_empty() {
+        eprintln!("The password cannot be empty");
+        std::process::exit(1);
+    }
+    password
+}
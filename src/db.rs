@@ -13,14 +13,22 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use crate::auth::Role;
 use crate::error::Result;
+use crate::modes::GameMode;
 use crate::protos::gamelog::GameLog;
-use mongodb::bson::{bson, doc};
+use futures::StreamExt;
+use mongodb::bson::{bson, doc, oid::ObjectId};
 use mongodb::{
-    bson::{Binary, Bson},
+    bson::{Binary, Bson, Document},
+    options::{FindOptions, ReplaceOptions},
     Client, Database,
 };
 use protobuf::Message;
+use strum_macros::{Display, EnumIter, EnumString};
+
+/// Version of the indexed metadata. Logs indexed with an older version are indexed again.
+const INDEX_VERSION: i32 = 6;
 
 pub struct DbHandle {
     client: Database,
@@ -29,6 +37,210 @@ pub struct DbHandle {
 #[derive(Clone)]
 pub struct GameLogMeta {
     pub server: Option<String>,
+    /// Set when staff hid the game from public view
+    pub hidden: Option<HiddenLog>,
+    /// Whether search metadata was ever stored for the log, even if outdated
+    pub indexed: bool,
+}
+
+/// Why and by whom a log was hidden from public view
+#[derive(Clone)]
+pub struct HiddenLog {
+    pub reason: String,
+    pub by: String,
+    /// Time the log was hidden, in milliseconds
+    pub at: i64,
+}
+
+/// A staff action, as recorded in the audit log
+pub struct AuditEntry {
+    /// Name of the action, e.g. `hide`
+    pub action: String,
+    /// What the action applied to, e.g. `cai/3kT9` for a game
+    pub target: String,
+    pub reason: String,
+    /// What the action changed, when the target alone does not say, e.g. how many names were
+    /// replaced
+    pub details: String,
+    /// Name of the staff member who took the action
+    pub user: String,
+    /// Time of the action, in milliseconds
+    pub time: i64,
+}
+
+/// Searchable metadata, stored in the `index` field alongside a log's protobuf data
+pub struct GameLogIndex {
+    pub start: i64,
+    pub end: i64,
+    /// Maps of the game, or Gravity stages in play order
+    pub maps: Vec<String>,
+    /// Names of the participants
+    pub players: Vec<String>,
+    /// Names of the winning players, or of the winning team
+    pub winners: Vec<String>,
+    /// Lowercase names, nicks and UUIDs of the participants, for lookups
+    pub player_keys: Vec<String>,
+    /// Lowercase names and UUIDs of the winners, for lookups
+    pub winner_keys: Vec<String>,
+}
+
+/// A search result: a log's indexed metadata, without its data
+pub struct IndexedLog {
+    pub id: Vec<u8>,
+    pub server: Option<String>,
+    pub index: GameLogIndex,
+}
+
+/// What the site pages need to know about a game, stored in the `index.page` field so that
+/// pages do not parse logs. Nicked players are only known by their nick.
+pub struct IndexedGame {
+    pub mode: GameMode,
+    pub id: String,
+    pub start: i64,
+    pub duration: i32,
+    pub map: String,
+    /// One-line outcome, e.g. "Red won 3–1 on Tumbleweed in 12:04"
+    pub description: String,
+    /// Names of the winning players, or of the winning team
+    pub winners: Vec<String>,
+    pub players: Vec<IndexedPlayer>,
+    pub kills: Vec<IndexedKill>,
+    /// Gravity stages, in play order
+    pub stages: Vec<IndexedStage>,
+    /// Server the game was played on, once read back from the database
+    pub server: Option<String>,
+}
+
+pub struct IndexedPlayer {
+    pub uuid: String,
+    pub name: String,
+    /// Whether the name is a nick
+    pub nicked: bool,
+    pub team: String,
+    pub won: bool,
+}
+
+/// A kill between two players of the game, by UUID
+pub struct IndexedKill {
+    pub killer: String,
+    pub victim: String,
+}
+
+/// How the players of a game did on a Gravity stage
+pub struct IndexedStage {
+    pub name: String,
+    /// Players who reached the stage
+    pub players: u32,
+    /// Player names and times of the completions that were not skipped, in nanoseconds
+    pub completions: Vec<(String, u64)>,
+    pub skips: u32,
+    pub hardcore_fails: u32,
+}
+
+/// Totals over the public games of a mode
+pub struct PublicStats {
+    pub games: u64,
+    /// UUIDs of the players, as shown on public pages
+    pub players: Vec<String>,
+    /// Games played on each map, or on each Gravity stage
+    pub maps: Vec<(String, u32)>,
+}
+
+/// A staff member who can sign in
+#[derive(Clone)]
+pub struct StaffUser {
+    pub name: String,
+    pub role: Role,
+    /// Password hash, as produced by [`crate::auth::hash_password`]
+    pub password: String,
+}
+
+/// A chat message, indexed for searches
+pub struct ChatLine {
+    /// Index of the event in the log, as used in event permalinks
+    pub event: u32,
+    /// Time since the start of the game, in milliseconds
+    pub time: i32,
+    pub sender: String,
+    pub message: String,
+    /// Name of the channel, empty for the global chat
+    pub channel: String,
+}
+
+/// A chat search result
+pub struct ChatResult {
+    pub game: String,
+    pub id: Vec<u8>,
+    /// Start of the game, in milliseconds
+    pub start: i64,
+    pub line: ChatLine,
+}
+
+/// An attempt at delivering a webhook
+pub struct WebhookDelivery {
+    pub url: String,
+    pub game: String,
+    /// Base62 ID of the game, as in its URL
+    pub game_id: String,
+    /// Number of the attempt, starting from 1
+    pub attempt: u32,
+    /// HTTP status of the answer, unset if there was none
+    pub status: Option<u16>,
+    /// Why the attempt failed, empty if it succeeded
+    pub error: String,
+    /// Time of the attempt, in milliseconds
+    pub time: i64,
+}
+
+/// A player report of an event in a game
+pub struct Report {
+    /// Hex ID of the stored report, unset for new ones
+    pub id: Option<String>,
+    pub game: String,
+    pub game_id: Vec<u8>,
+    /// Index of the event in the log, as used in event permalinks
+    pub event: u32,
+    pub reason: String,
+    /// Time of the report, in milliseconds
+    pub time: i64,
+    pub status: ReportStatus,
+    /// Name of the staff member who handled the report
+    pub handled_by: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, EnumString, Display, EnumIter)]
+#[strum(serialize_all = "lowercase")]
+pub enum ReportStatus {
+    /// Waiting for a moderator
+    Open,
+    /// Action was taken
+    Resolved,
+    /// No action was needed
+    Dismissed,
+}
+
+/// Filters for searching chat messages. Unset fields match every message.
+#[derive(Default)]
+pub struct ChatFilter {
+    pub game: Option<String>,
+    /// Words or phrase the message must contain
+    pub phrase: Option<String>,
+    pub sender: Option<String>,
+}
+
+/// Filters for searching logs by their indexed metadata. Unset fields match every log.
+#[derive(Default)]
+pub struct GameLogFilter {
+    /// Minimum game start, in milliseconds
+    pub after: Option<i64>,
+    /// Maximum game start, in milliseconds
+    pub before: Option<i64>,
+    pub server: Option<String>,
+    pub map: Option<String>,
+    /// Name or UUID of a winning player, or name of the winning team
+    pub winner: Option<String>,
+    /// Name or UUID of a participant
+    pub player: Option<String>,
 }
 
 impl DbHandle {
@@ -55,15 +267,908 @@ impl DbHandle {
             .collection(&format!("gamelogs_{}", game))
             .find_one(filter, None)
             .await?
-            .map(|doc| {
-                Ok((
-                    GameLog::parse_from_bytes(&doc.get_binary_generic("data")?).unwrap(),
-                    GameLogMeta {
-                        server: doc.get_str("server").map(Into::into).ok(),
+            .map(|doc| Self::parse_log(&doc));
+        res.transpose()
+    }
+
+    /// Checks whether a log with the given ID is stored for the given game.
+    pub async fn has_game_log(&self, game: &str, id: Vec<u8>) -> Result<bool> {
+        let filter = doc! {"game_id": Self::bytes(id)};
+        let count = self
+            .client
+            .collection(&format!("gamelogs_{}", game))
+            .count_documents(filter, None)
+            .await?;
+        Ok(count > 0)
+    }
+
+    /// Retrieves every stored log for the given game, alongside its ID.
+    pub async fn game_logs(&self, game: &str) -> Result<Vec<(Vec<u8>, GameLog, GameLogMeta)>> {
+        self.find_game_logs(game, None, None).await
+    }
+
+    /// Retrieves the logs of the given game that may contain a player, by their lowercase name
+    /// or dashed UUID: the indexed logs where they played or chatted, and the logs that are not
+    /// indexed yet or could not be indexed.
+    pub async fn player_game_logs(
+        &self,
+        game: &str,
+        key: &str,
+    ) -> Result<Vec<(Vec<u8>, GameLog, GameLogMeta)>> {
+        let chatted = self
+            .client
+            .collection("chat_index")
+            .distinct("game_id", doc! {"game": game, "sender_key": key}, None)
+            .await?;
+        self.find_game_logs(
+            game,
+            doc! {"$or": [
+                {"index.player_keys": key},
+                {"game_id": {"$in": chatted}},
+                {"index.version": {"$ne": INDEX_VERSION}},
+                {"index.error": {"$exists": true}},
+            ]},
+            None,
+        )
+        .await
+    }
+
+    /// Retrieves up to `limit` logs of the given game that have no up-to-date indexed
+    /// metadata. Indexing them removes them from the next batch.
+    pub async fn unindexed_game_logs(
+        &self,
+        game: &str,
+        limit: i64,
+    ) -> Result<Vec<(Vec<u8>, GameLog, GameLogMeta)>> {
+        let options = FindOptions::builder().limit(limit).build();
+        self.find_game_logs(
+            game,
+            doc! {"index.version": {"$ne": INDEX_VERSION}},
+            options,
+        )
+        .await
+    }
+
+    async fn find_game_logs(
+        &self,
+        game: &str,
+        filter: impl Into<Option<Document>>,
+        options: impl Into<Option<FindOptions>>,
+    ) -> Result<Vec<(Vec<u8>, GameLog, GameLogMeta)>> {
+        let mut cursor = self
+            .client
+            .collection(&format!("gamelogs_{}", game))
+            .find(filter, options)
+            .await?;
+        let mut res = vec![];
+        while let Some(doc) = cursor.next().await {
+            let doc = doc?;
+            let (log, meta) = Self::parse_log(&doc)?;
+            res.push((doc.get_binary_generic("game_id")?.clone(), log, meta));
+        }
+        Ok(res)
+    }
+
+    /// Stores the indexed metadata of a log, and what its public page shows.
+    pub async fn set_game_log_index(
+        &self,
+        game: &str,
+        id: Vec<u8>,
+        index: &GameLogIndex,
+        page: &IndexedGame,
+    ) -> Result<()> {
+        let index = doc! {
+            "version": INDEX_VERSION,
+            "start": index.start,
+            "end": index.end,
+            "maps": &index.maps,
+            "map_keys": index.maps.iter().map(|m| m.to_lowercase()).collect::<Vec<_>>(),
+            "players": &index.players,
+            "winners": &index.winners,
+            "player_keys": &index.player_keys,
+            "winner_keys": &index.winner_keys,
+            "page": Self::page_doc(page),
+        };
+        self.client
+            .collection(&format!("gamelogs_{}", game))
+            .update_one(
+                doc! {"game_id": Self::bytes(id)},
+                doc! {"$set": {"index": index}},
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Marks a log that could not be indexed, with the error, so that it is only tried again
+    /// with the next index version.
+    pub async fn set_game_log_index_error(
+        &self,
+        game: &str,
+        id: Vec<u8>,
+        error: &str,
+    ) -> Result<()> {
+        self.client
+            .collection(&format!("gamelogs_{}", game))
+            .update_one(
+                doc! {"game_id": Self::bytes(id)},
+                doc! {"$set": {"index": {"version": INDEX_VERSION, "error": error}}},
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Hides a log from public view, or shows it again. Returns whether the log exists.
+    pub async fn set_game_log_hidden(
+        &self,
+        game: &str,
+        id: Vec<u8>,
+        hidden: Option<&HiddenLog>,
+    ) -> Result<bool> {
+        let update = match hidden {
+            Some(hidden) => doc! {"$set": {"hidden": {
+                "reason": &hidden.reason,
+                "by": &hidden.by,
+                "at": hidden.at,
+            }}},
+            None => doc! {"$unset": {"hidden": ""}},
+        };
+        let res = self
+            .client
+            .collection(&format!("gamelogs_{}", game))
+            .update_one(doc! {"game_id": Self::bytes(id)}, update, None)
+            .await?;
+        Ok(res.matched_count > 0)
+    }
+
+    /// Replaces the data of a log. Its indexed chat messages and page are dropped and its
+    /// metadata is marked as outdated, so that the log is indexed again.
+    pub async fn replace_game_log_data(
+        &self,
+        game: &str,
+        id: Vec<u8>,
+        log: &GameLog,
+    ) -> Result<()> {
+        self.client
+            .collection("chat_index")
+            .delete_many(
+                doc! {"game": game, "game_id": Self::bytes(id.clone())},
+                None,
+            )
+            .await?;
+        self.client
+            .collection(&format!("gamelogs_{}", game))
+            .update_one(
+                doc! {"game_id": Self::bytes(id)},
+                doc! {
+                    "$set": {
+                        "data": Self::bytes(log.write_to_bytes()?),
+                        "index.version": 0,
                     },
-                ))
+                    "$unset": {"index.page": ""},
+                },
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Deletes a log and its indexed chat messages. Returns whether the log existed.
+    pub async fn delete_game_log(&self, game: &str, id: Vec<u8>) -> Result<bool> {
+        self.client
+            .collection("chat_index")
+            .delete_many(
+                doc! {"game": game, "game_id": Self::bytes(id.clone())},
+                None,
+            )
+            .await?;
+        let res = self
+            .client
+            .collection(&format!("gamelogs_{}", game))
+            .delete_one(doc! {"game_id": Self::bytes(id)}, None)
+            .await?;
+        Ok(res.deleted_count > 0)
+    }
+
+    /// Records a staff action in the audit log.
+    pub async fn add_audit_entry(&self, entry: &AuditEntry) -> Result<()> {
+        self.client
+            .collection("audit_log")
+            .insert_one(
+                doc! {
+                    "action": &entry.action,
+                    "target": &entry.target,
+                    "reason": &entry.reason,
+                    "details": &entry.details,
+                    "user": &entry.user,
+                    "time": entry.time,
+                },
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Retrieves the most recent staff actions, newest first.
+    pub async fn audit_entries(&self, limit: i64) -> Result<Vec<AuditEntry>> {
+        let options = FindOptions::builder()
+            .sort(doc! {"time": -1})
+            .limit(limit)
+            .build();
+        let mut cursor = self
+            .client
+            .collection("audit_log")
+            .find(None, options)
+            .await?;
+        let mut res = vec![];
+        while let Some(doc) = cursor.next().await {
+            let doc = doc?;
+            res.push(AuditEntry {
+                action: doc.get_str("action")?.into(),
+                target: doc.get_str("target")?.into(),
+                reason: doc.get_str("reason")?.into(),
+                details: doc.get_str("details").unwrap_or_default().into(),
+                user: doc.get_str("user")?.into(),
+                time: doc.get_i64("time")?,
             });
-        res.transpose()
+        }
+        Ok(res)
+    }
+
+    /// Records an attempt at delivering a webhook.
+    pub async fn add_webhook_delivery(&self, delivery: &WebhookDelivery) -> Result<()> {
+        self.client
+            .collection("webhook_deliveries")
+            .insert_one(
+                doc! {
+                    "url": &delivery.url,
+                    "game": &delivery.game,
+                    "game_id": &delivery.game_id,
+                    "attempt": delivery.attempt as i32,
+                    "status": delivery.status.map_or(Bson::Null, |s| Bson::Int32(s as i32)),
+                    "error": &delivery.error,
+                    "time": delivery.time,
+                },
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Retrieves the most recent webhook delivery attempts, newest first.
+    pub async fn webhook_deliveries(&self, limit: i64) -> Result<Vec<WebhookDelivery>> {
+        let options = FindOptions::builder()
+            .sort(doc! {"time": -1})
+            .limit(limit)
+            .build();
+        let mut cursor = self
+            .client
+            .collection("webhook_deliveries")
+            .find(None, options)
+            .await?;
+        let mut res = vec![];
+        while let Some(doc) = cursor.next().await {
+            let doc = doc?;
+            res.push(WebhookDelivery {
+                url: doc.get_str("url")?.into(),
+                game: doc.get_str("game")?.into(),
+                game_id: doc.get_str("game_id")?.into(),
+                attempt: doc.get_i32("attempt")? as u32,
+                status: doc.get_i32("status").ok().map(|s| s as u16),
+                error: doc.get_str("error")?.into(),
+                time: doc.get_i64("time")?,
+            });
+        }
+        Ok(res)
+    }
+
+    /// Stores a new player report.
+    pub async fn add_report(&self, report: &Report) -> Result<()> {
+        self.client
+            .collection("reports")
+            .insert_one(
+                doc! {
+                    "game": &report.game,
+                    "game_id": Self::bytes(report.game_id.clone()),
+                    "event": report.event as i64,
+                    "reason": &report.reason,
+                    "time": report.time,
+                    "status": report.status.to_string(),
+                },
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Retrieves the most recent reports with the given status, newest first.
+    pub async fn reports(&self, status: ReportStatus, limit: i64) -> Result<Vec<Report>> {
+        let options = FindOptions::builder()
+            .sort(doc! {"time": -1})
+            .limit(limit)
+            .build();
+        let mut cursor = self
+            .client
+            .collection("reports")
+            .find(doc! {"status": status.to_string()}, options)
+            .await?;
+        let mut res = vec![];
+        while let Some(doc) = cursor.next().await {
+            let doc = doc?;
+            res.push(Report {
+                id: Some(doc.get_object_id("_id")?.to_hex()),
+                game: doc.get_str("game")?.into(),
+                game_id: doc.get_binary_generic("game_id")?.clone(),
+                event: doc.get_i64("event")? as u32,
+                reason: doc.get_str("reason")?.into(),
+                time: doc.get_i64("time")?,
+                status,
+                handled_by: doc.get_str("handled_by").map(Into::into).ok(),
+            });
+        }
+        Ok(res)
+    }
+
+    /// Changes the status of a report. Returns whether the report exists.
+    pub async fn set_report_status(
+        &self,
+        id: &str,
+        status: ReportStatus,
+        handled_by: &str,
+    ) -> Result<bool> {
+        let id = match ObjectId::with_string(id) {
+            Ok(id) => id,
+            Err(_) => return Ok(false),
+        };
+        let res = self
+            .client
+            .collection("reports")
+            .update_one(
+                doc! {"_id": id},
+                doc! {"$set": {"status": status.to_string(), "handled_by": handled_by}},
+                None,
+            )
+            .await?;
+        Ok(res.matched_count > 0)
+    }
+
+    /// Replaces the indexed chat messages of a log.
+    pub async fn set_chat_index(
+        &self,
+        game: &str,
+        id: Vec<u8>,
+        start: i64,
+        lines: &[ChatLine],
+    ) -> Result<()> {
+        let collection = self.client.collection("chat_index");
+        collection
+            .delete_many(
+                doc! {"game": game, "game_id": Self::bytes(id.clone())},
+                None,
+            )
+            .await?;
+        if lines.is_empty() {
+            return Ok(());
+        }
+        let docs = lines.iter().map(|line| {
+            doc! {
+                "game": game,
+                "game_id": Self::bytes(id.clone()),
+                "start": start,
+                "event": line.event as i64,
+                "time": line.time,
+                "sender": &line.sender,
+                "sender_key": line.sender.to_lowercase(),
+                "message": &line.message,
+                "channel": &line.channel,
+            }
+        });
+        collection.insert_many(docs, None).await?;
+        Ok(())
+    }
+
+    /// Creates the indexes used by chat searches, if they do not exist.
+    pub async fn create_chat_indexes(&self) -> Result<()> {
+        self.client
+            .run_command(
+                doc! {
+                    "createIndexes": "chat_index",
+                    "indexes": [
+                        {"key": {"message": "text"}, "name": "message_text"},
+                        {"key": {"sender_key": 1, "start": -1}, "name": "sender_start"},
+                        {"key": {"game": 1, "game_id": 1}, "name": "game_id"},
+                    ],
+                },
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Searches indexed chat messages, newest games first.
+    pub async fn search_chat(&self, filter: &ChatFilter, limit: i64) -> Result<Vec<ChatResult>> {
+        let mut query = Document::new();
+        if let Some(game) = &filter.game {
+            query.insert("game", game);
+        }
+        if let Some(phrase) = &filter.phrase {
+            // Quoted, the text is matched as a whole phrase rather than as any of its words
+            query.insert(
+                "$text",
+                doc! {"$search": format!("\"{}\"", phrase.replace('"', ""))},
+            );
+        }
+        if let Some(sender) = &filter.sender {
+            query.insert("sender_key", sender.to_lowercase());
+        }
+        let options = FindOptions::builder()
+            .sort(doc! {"start": -1, "event": 1})
+            .limit(limit)
+            .build();
+        let mut cursor = self
+            .client
+            .collection("chat_index")
+            .find(query, options)
+            .await?;
+        let mut res = vec![];
+        while let Some(doc) = cursor.next().await {
+            let doc = doc?;
+            res.push(ChatResult {
+                game: doc.get_str("game")?.into(),
+                id: doc.get_binary_generic("game_id")?.clone(),
+                start: doc.get_i64("start")?,
+                line: ChatLine {
+                    event: doc.get_i64("event")? as u32,
+                    time: doc.get_i32("time")?,
+                    sender: doc.get_str("sender")?.into(),
+                    message: doc.get_str("message")?.into(),
+                    channel: doc.get_str("channel")?.into(),
+                },
+            });
+        }
+        Ok(res)
+    }
+
+    /// Creates the collection indexes used by log searches, if they do not exist.
+    pub async fn create_search_indexes(&self, game: &str) -> Result<()> {
+        self.client
+            .run_command(
+                doc! {
+                    "createIndexes": format!("gamelogs_{}", game),
+                    "indexes": [
+                        {"key": {"index.start": -1}, "name": "index_start"},
+                        {"key": {"server": 1, "index.start": -1}, "name": "server_start"},
+                        {"key": {"index.map_keys": 1}, "name": "index_map_keys"},
+                        {"key": {"index.player_keys": 1}, "name": "index_player_keys"},
+                        {"key": {"index.winner_keys": 1}, "name": "index_winner_keys"},
+                        {"key": {"index.page.keys": 1, "index.start": -1}, "name": "index_page_keys"},
+                    ],
+                },
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Searches the logs of the given game by their indexed metadata, newest first.
+    pub async fn search_game_logs(
+        &self,
+        game: &str,
+        filter: &GameLogFilter,
+        limit: i64,
+    ) -> Result<Vec<IndexedLog>> {
+        // Logs that could not be indexed have no metadata to search
+        let mut query = doc! {"index.start": {"$exists": true}};
+        let mut start = Document::new();
+        if let Some(after) = filter.after {
+            start.insert("$gte", after);
+        }
+        if let Some(before) = filter.before {
+            start.insert("$lte", before);
+        }
+        if !start.is_empty() {
+            query.insert("index.start", start);
+        }
+        if let Some(server) = &filter.server {
+            query.insert("server", server);
+        }
+        if let Some(map) = &filter.map {
+            query.insert("index.map_keys", map.to_lowercase());
+        }
+        if let Some(winner) = &filter.winner {
+            query.insert("index.winner_keys", winner.to_lowercase());
+        }
+        if let Some(player) = &filter.player {
+            query.insert("index.player_keys", player.to_lowercase());
+        }
+        let options = FindOptions::builder()
+            .sort(doc! {"index.start": -1})
+            .limit(limit)
+            .projection(doc! {"data": 0})
+            .build();
+        let mut cursor = self
+            .client
+            .collection(&format!("gamelogs_{}", game))
+            .find(query, options)
+            .await?;
+        let mut res = vec![];
+        while let Some(doc) = cursor.next().await {
+            let doc = doc?;
+            res.push(IndexedLog {
+                id: doc.get_binary_generic("game_id")?.clone(),
+                server: doc.get_str("server").map(Into::into).ok(),
+                index: Self::parse_index(doc.get_document("index")?)?,
+            });
+        }
+        Ok(res)
+    }
+
+    /// Retrieves the stored pages of the public logs of a game mode, newest first. With
+    /// `players`, only the games of any of these players, by lowercase name or UUID as shown on
+    /// public pages.
+    pub async fn public_games(
+        &self,
+        mode: GameMode,
+        players: &[&str],
+        limit: Option<i64>,
+    ) -> Result<Vec<IndexedGame>> {
+        let mut query = Self::public_query();
+        if !players.is_empty() {
+            query.insert("index.page.keys", doc! {"$in": players});
+        }
+        let options = FindOptions::builder()
+            .sort(doc! {"index.start": -1})
+            .projection(doc! {"server": 1, "index.start": 1, "index.page": 1})
+            .limit(limit)
+            .build();
+        let mut cursor = self
+            .client
+            .collection(&format!("gamelogs_{}", mode.get_database_id()))
+            .find(query, options)
+            .await?;
+        let mut res = vec![];
+        while let Some(doc) = cursor.next().await {
+            let doc = doc?;
+            let index = doc.get_document("index")?;
+            let mut game =
+                Self::parse_page(mode, index.get_i64("start")?, index.get_document("page")?)?;
+            game.server = doc.get_str("server").map(Into::into).ok();
+            res.push(game);
+        }
+        Ok(res)
+    }
+
+    /// Checks whether a log with the given ID has a public page.
+    pub async fn has_public_game(&self, mode: GameMode, id: Vec<u8>) -> Result<bool> {
+        let mut query = Self::public_query();
+        query.insert("game_id", Self::bytes(id));
+        let count = self
+            .client
+            .collection(&format!("gamelogs_{}", mode.get_database_id()))
+            .count_documents(query, None)
+            .await?;
+        Ok(count > 0)
+    }
+
+    /// Counts the public games of a game mode, their players and maps.
+    pub async fn public_stats(&self, mode: GameMode) -> Result<PublicStats> {
+        let collection = self
+            .client
+            .collection(&format!("gamelogs_{}", mode.get_database_id()));
+        let games = collection
+            .count_documents(Self::public_query(), None)
+            .await?;
+        let players = collection
+            .distinct("index.page.players.uuid", Self::public_query(), None)
+            .await?;
+        let mut cursor = collection
+            .aggregate(
+                vec![
+                    doc! {"$match": Self::public_query()},
+                    doc! {"$unwind": "$index.maps"},
+                    doc! {"$group": {"_id": "$index.maps", "games": {"$sum": 1}}},
+                ],
+                None,
+            )
+            .await?;
+        let mut maps = vec![];
+        while let Some(doc) = cursor.next().await {
+            let doc = doc?;
+            maps.push((doc.get_str("_id")?.into(), doc.get_i32("games")? as u32));
+        }
+        Ok(PublicStats {
+            games: games as u64,
+            players: players
+                .iter()
+                .filter_map(|p| p.as_str().map(Into::into))
+                .collect(),
+            maps,
+        })
+    }
+
+    /// Names shown on public pages that start with a prefix, in any case
+    pub async fn public_player_names(&self, mode: GameMode, prefix: &str) -> Result<Vec<String>> {
+        let mut query = Self::public_query();
+        query.insert(
+            "index.page.keys",
+            doc! {"$regex": format!("^{}", regex::escape(&prefix.to_lowercase()))},
+        );
+        let names = self
+            .client
+            .collection(&format!("gamelogs_{}", mode.get_database_id()))
+            .distinct("index.page.players.name", query, None)
+            .await?;
+        Ok(names
+            .iter()
+            .filter_map(|n| n.as_str())
+            .filter(|n| n.to_lowercase().starts_with(&prefix.to_lowercase()))
+            .map(Into::into)
+            .collect())
+    }
+
+    /// Matches the logs with a public page
+    fn public_query() -> Document {
+        doc! {"hidden": {"$exists": false}, "index.page": {"$exists": true}}
+    }
+
+    fn page_doc(page: &IndexedGame) -> Document {
+        doc! {
+            "id": &page.id,
+            // Lowercase names and UUIDs of the players, for lookups from public pages
+            "keys": page
+                .players
+                .iter()
+                .flat_map(|p| [p.uuid.to_lowercase(), p.name.to_lowercase()])
+                .collect::<Vec<_>>(),
+            "duration": page.duration,
+            "map": &page.map,
+            "description": &page.description,
+            "winners": &page.winners,
+            "players": page.players.iter().map(|p| doc! {
+                "uuid": &p.uuid,
+                "name": &p.name,
+                "nicked": p.nicked,
+                "team": &p.team,
+                "won": p.won,
+            }).collect::<Vec<_>>(),
+            "kills": page.kills.iter().map(|k| doc! {
+                "killer": &k.killer,
+                "victim": &k.victim,
+            }).collect::<Vec<_>>(),
+            "stages": page.stages.iter().map(|s| doc! {
+                "name": &s.name,
+                "players": s.players as i64,
+                "completions": s.completions.iter().map(|(player, time)| doc! {
+                    "player": player,
+                    "time": *time as i64,
+                }).collect::<Vec<_>>(),
+                "skips": s.skips as i64,
+                "hardcore_fails": s.hardcore_fails as i64,
+            }).collect::<Vec<_>>(),
+        }
+    }
+
+    fn parse_page(mode: GameMode, start: i64, doc: &Document) -> Result<IndexedGame> {
+        let docs = |doc: &Document, key: &str| -> Result<Vec<Document>> {
+            Ok(doc
+                .get_array(key)?
+                .iter()
+                .filter_map(|d| d.as_document().cloned())
+                .collect())
+        };
+        Ok(IndexedGame {
+            mode,
+            id: doc.get_str("id")?.into(),
+            start,
+            duration: doc.get_i32("duration")?,
+            map: doc.get_str("map")?.into(),
+            description: doc.get_str("description")?.into(),
+            winners: doc
+                .get_array("winners")?
+                .iter()
+                .filter_map(|s| s.as_str().map(Into::into))
+                .collect(),
+            players: docs(doc, "players")?
+                .iter()
+                .map(|p| {
+                    Ok(IndexedPlayer {
+                        uuid: p.get_str("uuid")?.into(),
+                        name: p.get_str("name")?.into(),
+                        nicked: p.get_bool("nicked")?,
+                        team: p.get_str("team")?.into(),
+                        won: p.get_bool("won")?,
+                    })
+                })
+                .collect::<Result<_>>()?,
+            kills: docs(doc, "kills")?
+                .iter()
+                .map(|k| {
+                    Ok(IndexedKill {
+                        killer: k.get_str("killer")?.into(),
+                        victim: k.get_str("victim")?.into(),
+                    })
+                })
+                .collect::<Result<_>>()?,
+            stages: docs(doc, "stages")?
+                .iter()
+                .map(|s| {
+                    Ok(IndexedStage {
+                        name: s.get_str("name")?.into(),
+                        players: s.get_i64("players")? as u32,
+                        completions: docs(s, "completions")?
+                            .iter()
+                            .map(|c| Ok((c.get_str("player")?.into(), c.get_i64("time")? as u64)))
+                            .collect::<Result<_>>()?,
+                        skips: s.get_i64("skips")? as u32,
+                        hardcore_fails: s.get_i64("hardcore_fails")? as u32,
+                    })
+                })
+                .collect::<Result<_>>()?,
+            server: None,
+        })
+    }
+
+    fn parse_index(doc: &Document) -> Result<GameLogIndex> {
+        let strings = |key: &str| -> Result<Vec<String>> {
+            Ok(doc
+                .get_array(key)?
+                .iter()
+                .filter_map(|s| s.as_str().map(Into::into))
+                .collect())
+        };
+        Ok(GameLogIndex {
+            start: doc.get_i64("start")?,
+            end: doc.get_i64("end")?,
+            maps: strings("maps")?,
+            players: strings("players")?,
+            winners: strings("winners")?,
+            player_keys: strings("player_keys")?,
+            winner_keys: strings("winner_keys")?,
+        })
+    }
+
+    /// Retrieves a staff member by name, ignoring case.
+    pub async fn staff_user(&self, name: &str) -> Result<Option<StaffUser>> {
+        self.client
+            .collection("staff_users")
+            .find_one(doc! {"key": name.to_lowercase()}, None)
+            .await?
+            .map(|doc| Self::parse_staff_user(&doc))
+            .transpose()
+    }
+
+    /// Retrieves every staff member, sorted by name.
+    pub async fn staff_users(&self) -> Result<Vec<StaffUser>> {
+        let options = FindOptions::builder().sort(doc! {"key": 1}).build();
+        let mut cursor = self
+            .client
+            .collection("staff_users")
+            .find(None, options)
+            .await?;
+        let mut res = vec![];
+        while let Some(doc) = cursor.next().await {
+            res.push(Self::parse_staff_user(&doc?)?);
+        }
+        Ok(res)
+    }
+
+    /// Creates a staff member, or replaces the one with the same name.
+    pub async fn save_staff_user(&self, user: &StaffUser) -> Result<()> {
+        let key = user.name.to_lowercase();
+        self.client
+            .collection("staff_users")
+            .replace_one(
+                doc! {"key": &key},
+                doc! {
+                    "key": &key,
+                    "name": &user.name,
+                    "role": user.role.to_string(),
+                    "password": &user.password,
+                },
+                ReplaceOptions::builder().upsert(true).build(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Removes a staff member and signs them out. Returns whether they existed.
+    pub async fn remove_staff_user(&self, name: &str) -> Result<bool> {
+        self.delete_staff_sessions(name).await?;
+        let res = self
+            .client
+            .collection("staff_users")
+            .delete_one(doc! {"key": name.to_lowercase()}, None)
+            .await?;
+        Ok(res.deleted_count > 0)
+    }
+
+    /// Stores a session of a staff member, by the hash of its token.
+    pub async fn create_staff_session(
+        &self,
+        token_hash: &str,
+        name: &str,
+        expires: i64,
+    ) -> Result<()> {
+        let collection = self.client.collection("staff_sessions");
+        // Expired sessions are cleaned up when new ones are created
+        collection
+            .delete_many(doc! {"expires": {"$lt": Self::now()}}, None)
+            .await?;
+        collection
+            .insert_one(
+                doc! {"token": token_hash, "key": name.to_lowercase(), "expires": expires},
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Retrieves the staff member signed in with a session, if it has not expired.
+    pub async fn staff_session(&self, token_hash: &str) -> Result<Option<StaffUser>> {
+        let session = self
+            .client
+            .collection("staff_sessions")
+            .find_one(
+                doc! {"token": token_hash, "expires": {"$gte": Self::now()}},
+                None,
+            )
+            .await?;
+        match session {
+            Some(session) => self.staff_user(session.get_str("key")?).await,
+            None => Ok(None),
+        }
+    }
+
+    pub async fn delete_staff_session(&self, token_hash: &str) -> Result<()> {
+        self.client
+            .collection("staff_sessions")
+            .delete_one(doc! {"token": token_hash}, None)
+            .await?;
+        Ok(())
+    }
+
+    /// Signs a staff member out of every session.
+    pub async fn delete_staff_sessions(&self, name: &str) -> Result<()> {
+        self.client
+            .collection("staff_sessions")
+            .delete_many(doc! {"key": name.to_lowercase()}, None)
+            .await?;
+        Ok(())
+    }
+
+    fn parse_staff_user(doc: &Document) -> Result<StaffUser> {
+        Ok(StaffUser {
+            name: doc.get_str("name")?.into(),
+            // Unknown roles get the least access
+            role: doc.get_str("role")?.parse().unwrap_or(Role::Viewer),
+            password: doc.get_str("password")?.into(),
+        })
+    }
+
+    /// The current time, in milliseconds
+    pub fn now() -> i64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as i64)
+    }
+
+    fn parse_log(doc: &Document) -> Result<(GameLog, GameLogMeta)> {
+        Ok((
+            GameLog::parse_from_bytes(doc.get_binary_generic("data")?)?,
+            GameLogMeta {
+                server: doc.get_str("server").map(Into::into).ok(),
+                hidden: match doc.get_document("hidden") {
+                    Ok(hidden) => Some(HiddenLog {
+                        reason: hidden.get_str("reason")?.into(),
+                        by: hidden.get_str("by")?.into(),
+                        at: hidden.get_i64("at")?,
+                    }),
+                    Err(_) => None,
+                },
+                indexed: doc.contains_key("index"),
+            },
+        ))
     }
 
     #[inline]
@@ -74,3 +1179,62 @@ impl DbHandle {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{DbHandle, IndexedGame, IndexedKill, IndexedPlayer, IndexedStage};
+    use crate::modes::GameMode;
+
+    #[test]
+    fn stored_pages_read_back_unchanged() {
+        let page = IndexedGame {
+            mode: GameMode::GRAV,
+            id: "abc".into(),
+            start: 1_600_000_000_000,
+            duration: 300_000,
+            map: "Tunnel".into(),
+            description: "Alice won in 05:00".into(),
+            winners: vec!["Alice".into()],
+            players: vec![
+                IndexedPlayer {
+                    uuid: "01010101-0101-0101-0101-010101010101".into(),
+                    name: "Alice".into(),
+                    nicked: false,
+                    team: "Players".into(),
+                    won: true,
+                },
+                IndexedPlayer {
+                    uuid: "4c764cb6-248e-f933-7fc1-c8769d4b74fc".into(),
+                    name: "Shadow".into(),
+                    nicked: true,
+                    team: "Players".into(),
+                    won: false,
+                },
+            ],
+            kills: vec![IndexedKill {
+                killer: "01010101-0101-0101-0101-010101010101".into(),
+                victim: "4c764cb6-248e-f933-7fc1-c8769d4b74fc".into(),
+            }],
+            stages: vec![IndexedStage {
+                name: "Tunnel".into(),
+                players: 2,
+                completions: vec![("Alice".into(), 20_000_000_000)],
+                skips: 1,
+                hardcore_fails: 3,
+            }],
+            server: None,
+        };
+        let doc = DbHandle::page_doc(&page);
+        assert_eq!(
+            doc.get_array("keys").unwrap(),
+            &vec![
+                "01010101-0101-0101-0101-010101010101".into(),
+                "alice".into(),
+                "4c764cb6-248e-f933-7fc1-c8769d4b74fc".into(),
+                "shadow".into(),
+            ]
+        );
+        let read = DbHandle::parse_page(page.mode, page.start, &doc).unwrap();
+        assert_eq!(DbHandle::page_doc(&read), doc);
+    }
+}
@@ -15,7 +15,11 @@
 
 use std::fmt::Display;
 
-use actix_web::{dev::Body, http::StatusCode, HttpResponse, ResponseError};
+use actix_web::{
+    dev::Body,
+    http::{header, StatusCode},
+    HttpResponse, ResponseError,
+};
 use mongodb::bson::document::ValueAccessError;
 use protobuf::ProtobufError;
 
@@ -28,6 +32,17 @@ pub enum Error {
     Protobuf(ProtobufError),
     ModeNotFound,
     NotFound,
+    /// Games hidden from public view by staff
+    Removed,
+    Forbidden,
+    /// Staff pages visited without signing in
+    Unauthorized,
+    InvalidGameId,
+    /// Staff actions or reports submitted without a reason
+    MissingReason,
+    /// Too many reports or failed sign-ins from the same address or for the same account
+    TooManyRequests,
+    Render,
 }
 
 impl Display for Error {
@@ -40,15 +55,30 @@ impl ResponseError for Error {
     fn status_code(&self) -> actix_web::http::StatusCode {
         match self {
             Error::NotFound | Error::ModeNotFound => StatusCode::NOT_FOUND,
+            Error::Removed => StatusCode::GONE,
+            Error::Forbidden => StatusCode::FORBIDDEN,
+            Error::Unauthorized => StatusCode::FOUND,
+            Error::TooManyRequests => StatusCode::TOO_MANY_REQUESTS,
+            Error::InvalidGameId | Error::MissingReason => StatusCode::BAD_REQUEST,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
 
     fn error_response(&self) -> actix_web::HttpResponse {
+        if let Error::Unauthorized = self {
+            return HttpResponse::Found()
+                .header(header::LOCATION, "/staff/login")
+                .finish();
+        }
         HttpResponse::new(self.status_code()).set_body(Body::from_slice(
             match self {
                 Error::NotFound => "Not found",
+                Error::Removed => "This game has been removed",
                 Error::ModeNotFound => "Mode not found",
+                Error::Forbidden => "Forbidden",
+                Error::InvalidGameId => "Invalid game ID",
+                Error::MissingReason => "A reason is required",
+                Error::TooManyRequests => "Too many attempts. Please try again later.",
                 _ => "Internal error. Please contact the server's administrators.",
             }
             .as_bytes(),
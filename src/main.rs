@@ -1,5 +1,8 @@
 use actix_web::{middleware, App, HttpServer};
 use db::DbHandle;
+use maps::MapManifest;
+use redact::ChatRedaction;
+use webhooks::Webhooks;
 
 // Copyright (C) 2021 RoccoDev
 //
@@ -19,29 +22,55 @@ use db::DbHandle;
 use std::io;
 use std::sync::Arc;
 
+mod auth;
+mod cli;
 mod db;
 mod error;
+mod maps;
 mod modes;
+mod privacy;
 mod protos;
+mod redact;
 mod web;
+mod webhooks;
 
 #[derive(Clone)]
 pub struct AppState {
     pub db: Arc<DbHandle>,
+    pub maps: Arc<MapManifest>,
+    pub redaction: Arc<ChatRedaction>,
+    pub webhooks: Arc<Webhooks>,
 }
 
 #[actix_web::main]
 async fn main() -> io::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        return cli::run(&args).await;
+    }
+
     let port = std::env::var("KIG_PORT").unwrap_or_else(|_| String::from("3233"));
     let host = std::env::var("KIG_HOST").unwrap_or_else(|_| String::from("127.0.0.1"));
 
     // Application state
     let db = Arc::new(DbHandle::new().await.unwrap());
-    let state = AppState { db };
+    let maps = Arc::new(MapManifest::load());
+    let redaction = Arc::new(ChatRedaction::load());
+    let webhooks = Arc::new(Webhooks::load());
+    let state = AppState {
+        db,
+        maps,
+        redaction,
+        webhooks,
+    };
+
+    // Store search metadata for new logs in the background
+    actix_web::rt::spawn(web::index_logs(state.clone()));
 
     HttpServer::new(move || {
         App::new()
             .wrap(middleware::Compress::default())
+            .wrap(web::StaffSessions)
             .data(state.clone())
             .service(web::static_files())
             .service(web::static_files_fallback())
@@ -0,0 +1,153 @@
+// Copyright (C) 2021 RoccoDev
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::modes::GameMode;
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::HashSet;
+
+lazy_static::lazy_static! {
+    static ref MAP_ESCAPE_REGEX: Regex = Regex::new(r#"[^a-zA-Z0-9]"#).unwrap();
+}
+
+/// Map metadata, read from the TOML file at `KIG_MAP_MANIFEST` (`maps.toml` by default)
+#[derive(Deserialize, Default)]
+pub struct MapManifest {
+    /// Credits of the maps that do not list their own
+    #[serde(default)]
+    credits: Vec<Credit>,
+    /// Image shown when a map has no screenshot, relative to `img/`
+    fallback_image: Option<String>,
+    #[serde(default)]
+    maps: Vec<MapInfo>,
+    /// Screenshots found under `img/maps` on startup, relative to `img/`
+    #[serde(skip)]
+    images: HashSet<String>,
+}
+
+#[derive(Deserialize)]
+struct MapInfo {
+    /// Database ID of the mode the map is played in
+    mode: String,
+    /// Name of the map as found in game logs, also used for its URL and screenshot
+    name: String,
+    display_name: Option<String>,
+    /// Other names of the map in older game logs
+    #[serde(default)]
+    aliases: Vec<String>,
+    description: Option<String>,
+    credits: Option<Vec<Credit>>,
+    fallback_image: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct Credit {
+    pub name: String,
+    pub uuid: String,
+}
+
+/// What the pages show about a map, falling back to defaults for maps missing from the manifest
+pub struct MapEntry<'a> {
+    pub name: String,
+    pub slug: String,
+    pub description: Option<&'a str>,
+    pub credits: &'a [Credit],
+    /// Path of the screenshot, relative to `img/`
+    pub image: String,
+}
+
+impl MapManifest {
+    pub fn load() -> MapManifest {
+        let path = std::env::var("KIG_MAP_MANIFEST").unwrap_or_else(|_| String::from("maps.toml"));
+        let mut manifest = match std::fs::read_to_string(&path) {
+            Ok(text) => toml::from_str(&text)
+                .unwrap_or_else(|e| panic!("invalid map manifest {}: {}", path, e)),
+            Err(_) => MapManifest::default(),
+        };
+        manifest.images = map_images();
+        manifest
+    }
+
+    pub fn get(&self, mode: GameMode, name: &str) -> MapEntry<'_> {
+        let info = self.find(mode, name);
+        let slug = info.map_or_else(|| slug(name), |m| slug(&m.name));
+
+        let image = format!("maps/{}/{}.png", mode.get_database_id(), slug);
+        let image = if self.images.contains(&image) {
+            image
+        } else {
+            info.and_then(|m| m.fallback_image.clone())
+                .or_else(|| self.fallback_image.clone())
+                .unwrap_or_else(|| format!("maps/{}/default.png", mode.get_database_id()))
+        };
+        MapEntry {
+            name: info
+                .map(|m| m.display_name.as_deref().unwrap_or(&m.name))
+                .unwrap_or(name)
+                .to_string(),
+            slug,
+            description: info.and_then(|m| m.description.as_deref()),
+            credits: info
+                .and_then(|m| m.credits.as_deref())
+                .unwrap_or(&self.credits),
+            image,
+        }
+    }
+
+    /// The slug of a map, shared by all of its aliases
+    pub fn slug(&self, mode: GameMode, name: &str) -> String {
+        slug(self.find(mode, name).map_or(name, |m| m.name.as_str()))
+    }
+
+    fn find(&self, mode: GameMode, name: &str) -> Option<&MapInfo> {
+        let slug = slug(name);
+        self.maps.iter().find(|m| {
+            m.mode == mode.get_database_id()
+                && (self::slug(&m.name) == slug || m.aliases.iter().any(|a| self::slug(a) == slug))
+        })
+    }
+}
+
+/// Lists the screenshots in each mode's directory under `img/maps`
+fn map_images() -> HashSet<String> {
+    let mut images = HashSet::new();
+    for mode in std::fs::read_dir("img/maps")
+        .into_iter()
+        .flatten()
+        .flatten()
+    {
+        for file in std::fs::read_dir(mode.path())
+            .into_iter()
+            .flatten()
+            .flatten()
+        {
+            if let (Some(mode), Some(file)) = (mode.file_name().to_str(), file.file_name().to_str())
+            {
+                images.insert(format!("maps/{}/{}", mode, file));
+            }
+        }
+    }
+    images
+}
+
+/// The name of a map in URLs and file names: lowercase, alphanumeric only
+pub fn slug(name: &str) -> String {
+    if name.is_empty() {
+        return String::from("default");
+    }
+    let mut res: String = MAP_ESCAPE_REGEX.replace_all(name, "").into();
+    res.make_ascii_lowercase();
+    res
+}
@@ -1,10 +1,27 @@
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, strum_macros::EnumString)]
+use strum::IntoEnumIterator;
+use strum_macros::{EnumIter, EnumString};
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, EnumString, EnumIter)]
 #[strum(serialize_all = "UPPERCASE")]
 pub enum GameMode {
+    // Names the modes were previously known by are still accepted in URLs
+    #[strum(
+        serialize = "CAI",
+        serialize = "COWBOYS",
+        serialize = "COWBOYSANDINDIANS"
+    )]
     CAI,
+    #[strum(
+        serialize = "TIMV",
+        serialize = "TROUBLE",
+        serialize = "TROUBLEINMINEVILLE"
+    )]
     TIMV,
+    #[strum(serialize = "BP", serialize = "BLOCKPARTY")]
     BP,
+    #[strum(serialize = "GRAV", serialize = "GRAVITY")]
     GRAV,
+    #[strum(serialize = "BED", serialize = "BEDWARS")]
     BED,
     Halloween2023,
     Halloween2024,
@@ -25,6 +42,21 @@ impl GameMode {
         }
     }
 
+    pub fn get_description(self) -> &'static str {
+        match self {
+            GameMode::CAI => "Two teams protect their leader while trying to capture the other team's.",
+            GameMode::TIMV => {
+                "Innocents and detectives must find the traitors hiding among them before they are all killed."
+            }
+            GameMode::BP => "Stand on the right color before the floor disappears. The last players standing win.",
+            GameMode::GRAV => "Fall through a series of stages without hitting anything, and reach the bottom first.",
+            GameMode::BED => "Protect your team's bed and destroy the others'. Players without a bed do not respawn.",
+            GameMode::Halloween2023 | GameMode::Halloween2024 | GameMode::Halloween2025 => {
+                "A limited-time mode played during the Kig-o'-ween event."
+            }
+        }
+    }
+
     pub fn get_database_id(self) -> &'static str {
         match self {
             GameMode::CAI => "cai",
@@ -37,4 +69,19 @@ impl GameMode {
             GameMode::Halloween2025 => "halloween2025",
         }
     }
+
+    /// The year of the Halloween event the mode was played in
+    pub fn halloween_year(self) -> Option<u16> {
+        match self {
+            GameMode::Halloween2023 => Some(2023),
+            GameMode::Halloween2024 => Some(2024),
+            GameMode::Halloween2025 => Some(2025),
+            _ => None,
+        }
+    }
+
+    /// Every Halloween edition, oldest first
+    pub fn halloween_editions() -> impl Iterator<Item = GameMode> {
+        GameMode::iter().filter(|m| m.halloween_year().is_some())
+    }
 }
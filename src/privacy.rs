@@ -0,0 +1,467 @@
+// Copyright (C) 2021 RoccoDev
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{
+    error::Result,
+    modes::GameMode,
+    protos::{
+        bed, bp, cai,
+        gamelog::{exts, GameEvent, GameLog},
+        grav, halloween, herd, timv,
+    },
+};
+use protobuf::{
+    ext::ExtFieldOptional, reflect::ProtobufValue, types::ProtobufTypeMessage, Message,
+};
+use rand::{rngs::OsRng, RngCore};
+use regex::Regex;
+use sha2::{Digest, Sha256};
+use std::{collections::HashMap, fmt};
+
+/// Shown in place of the chat messages of erased players
+const ERASED_MESSAGE: &str = "[removed]";
+
+/// A player named in a privacy request
+pub enum PlayerQuery {
+    Uuid(Vec<u8>),
+    Name(String),
+}
+
+impl PlayerQuery {
+    /// Parses a UUID, with or without dashes, or else a name
+    pub fn parse(input: &str) -> PlayerQuery {
+        let hex = input.replace('-', "");
+        match hex::decode(&hex) {
+            Ok(uuid) if uuid.len() == 16 => PlayerQuery::Uuid(uuid),
+            _ => PlayerQuery::Name(input.into()),
+        }
+    }
+
+    /// The lowercase name or dashed UUID, as in the search keys of indexed logs
+    pub fn key(&self) -> String {
+        match self {
+            PlayerQuery::Uuid(uuid) => {
+                let hex = hex::encode(uuid);
+                format!(
+                    "{}-{}-{}-{}-{}",
+                    &hex[..8],
+                    &hex[8..12],
+                    &hex[12..16],
+                    &hex[16..20],
+                    &hex[20..]
+                )
+            }
+            PlayerQuery::Name(name) => name.to_lowercase(),
+        }
+    }
+}
+
+/// The names and UUID a player goes by in a log
+pub struct Identity {
+    /// Name, and nick if any, as written in the log
+    pub names: Vec<String>,
+    pub uuid: Option<Vec<u8>>,
+}
+
+/// Player names and UUIDs to replace in a log
+#[derive(Default)]
+pub struct Replacements {
+    pub names: HashMap<String, String>,
+    pub uuids: HashMap<Vec<u8>, Vec<u8>>,
+}
+
+/// Counts the replacements, e.g. `2 names and 1 UUID replaced`. The replaced names and UUIDs
+/// are left out, as they would link the anonymized games back to the player.
+impl fmt::Display for Replacements {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let count =
+            |n: usize, what: &str| format!("{} {}{}", n, what, if n == 1 { "" } else { "s" });
+        write!(
+            f,
+            "{} and {} replaced",
+            count(self.names.len(), "name"),
+            count(self.uuids.len(), "UUID")
+        )
+    }
+}
+
+impl Replacements {
+    fn is_empty(&self) -> bool {
+        self.names.is_empty() && self.uuids.is_empty()
+    }
+
+    fn rename(&self, name: &mut String) {
+        if let Some(replacement) = self.names.get(name.as_str()) {
+            *name = replacement.clone();
+        }
+    }
+}
+
+/// Hides the real identity of nicked players, for public pages.
+///
+/// Their name is replaced with their nick and their UUID with one derived from it, wherever
+/// the log names a player, including mode-specific events. The `nick` field of the players is kept.
+pub fn public_log(mode: GameMode, log: &GameLog) -> Result<GameLog> {
+    let mut replacements = Replacements::default();
+    for player in log.get_teams().iter().flat_map(|t| t.get_players()) {
+        if player.has_nick() && player.get_nick() != player.get_name() {
+            replacements
+                .names
+                .insert(player.get_name().into(), player.get_nick().into());
+            replacements
+                .uuids
+                .insert(player.get_uuid().to_vec(), nick_uuid(player.get_nick()));
+        }
+    }
+    rewrite_players(mode, log, &replacements)
+}
+
+/// Replaces player names and UUIDs in the fields that hold them: the players of the teams, a
+/// winning player, chat senders, joins, leaves and the players named by the events of the mode.
+/// Team names, the map and chat messages are left as they are.
+pub fn rewrite_players(
+    mode: GameMode,
+    log: &GameLog,
+    replacements: &Replacements,
+) -> Result<GameLog> {
+    let mut log = log.clone();
+    if replacements.is_empty() {
+        return Ok(log);
+    }
+    // The winner is a team, or a single player in modes without teams
+    if log.has_winner()
+        && !log
+            .get_teams()
+            .iter()
+            .any(|t| t.get_name() == log.get_winner())
+    {
+        replacements.rename(log.mut_winner());
+    }
+    for team in log.mut_teams().iter_mut() {
+        for player in team.mut_players().iter_mut() {
+            if let Some(uuid) = replacements.uuids.get(player.get_uuid()) {
+                player.set_uuid(uuid.clone());
+            }
+            replacements.rename(player.mut_name());
+            if player.has_nick() {
+                replacements.rename(player.mut_nick());
+            }
+        }
+    }
+    let r = replacements;
+    for event in log.mut_events().iter_mut().map(|e| e.mut_event()) {
+        rewrite_event(event, &exts::chat, |e| r.rename(e.mut_sender()))?;
+        rewrite_event(event, &exts::join, |e| r.rename(e.mut_player()))?;
+        rewrite_event(event, &exts::leave, |e| r.rename(e.mut_player()))?;
+        match mode {
+            GameMode::CAI => {
+                use cai::exts::*;
+                rewrite_event(event, &catch, |e| {
+                    r.rename(e.mut_leader());
+                    r.rename(e.mut_carrier());
+                })?;
+                rewrite_event(event, &escape, |e| {
+                    r.rename(e.mut_leader());
+                    if e.has_saver() {
+                        r.rename(e.mut_saver());
+                    }
+                })?;
+                rewrite_event(event, &capture, |e| {
+                    r.rename(e.mut_leader());
+                    r.rename(e.mut_carrier());
+                })?;
+                rewrite_event(event, &death, |e| {
+                    r.rename(e.mut_player());
+                    if e.has_killer() {
+                        r.rename(e.mut_killer());
+                    }
+                })?;
+            }
+            GameMode::TIMV => {
+                use timv::exts::*;
+                rewrite_event(event, &death, |e| {
+                    r.rename(e.mut_player());
+                    if e.has_killer() {
+                        r.rename(e.mut_killer());
+                    }
+                })?;
+                rewrite_event(event, &test, |e| r.rename(e.mut_player()))?;
+                rewrite_event(event, &body, |e| {
+                    r.rename(e.mut_player());
+                    r.rename(e.mut_identifier());
+                })?;
+                rewrite_event(event, &trap, |e| r.rename(e.mut_player()))?;
+                rewrite_event(event, &detective, |e| {
+                    r.rename(e.mut_player());
+                    r.rename(e.mut_identifier());
+                })?;
+                rewrite_event(event, &psychic, |e| {
+                    r.rename(e.mut_psychic());
+                    e.mut_reported().iter_mut().for_each(|n| r.rename(n));
+                })?;
+                rewrite_event(event, &shared_purchase, |e| r.rename(e.mut_purchaser()))?;
+            }
+            GameMode::BP => {
+                use bp::exts::*;
+                rewrite_event(event, &death, |e| {
+                    for player in e.mut_player().iter_mut() {
+                        r.rename(player.mut_name());
+                    }
+                })?;
+                rewrite_event(event, &powerup, |e| r.rename(e.mut_name()))?;
+                rewrite_event(event, &winners, |e| {
+                    e.mut_winner().iter_mut().for_each(|n| r.rename(n));
+                })?;
+            }
+            GameMode::GRAV => {
+                use grav::exts::*;
+                rewrite_event(event, &stage_completion, |e| r.rename(e.mut_player()))?;
+                rewrite_event(event, &game_finish, |e| r.rename(e.mut_player()))?;
+                rewrite_event(event, &hardcore_fail, |e| r.rename(e.mut_player()))?;
+            }
+            GameMode::BED => {
+                rewrite_event(event, &bed::exts::bed_destroy, |e| {
+                    if e.has_player() {
+                        r.rename(e.mut_player());
+                    }
+                })?;
+                rewrite_event(event, &herd::exts::death, |e| {
+                    r.rename(e.mut_player());
+                    if e.has_killer() {
+                        r.rename(e.mut_killer());
+                    }
+                })?;
+            }
+            GameMode::Halloween2023 | GameMode::Halloween2024 | GameMode::Halloween2025 => {
+                rewrite_event(event, &halloween::exts::death, |e| {
+                    r.rename(e.mut_player());
+                    if e.has_killer() {
+                        r.rename(e.mut_killer());
+                    }
+                })?;
+            }
+        }
+    }
+    Ok(log)
+}
+
+/// Applies a change to an extension of an event, if the event has it.
+///
+/// Extensions are stored in the unknown fields, so a changed one is serialized again in their place.
+fn rewrite_event<M>(
+    event: &mut GameEvent,
+    extension: &ExtFieldOptional<GameEvent, ProtobufTypeMessage<M>>,
+    rewrite: impl FnOnce(&mut M),
+) -> Result<()>
+where
+    M: Message + Clone + PartialEq + ProtobufValue,
+{
+    let original = match extension.get(event) {
+        Some(message) => message,
+        None => return Ok(()),
+    };
+    let mut message = original.clone();
+    rewrite(&mut message);
+    if message != original {
+        let fields = event.mut_unknown_fields();
+        if let Some(fields) = &mut fields.fields {
+            fields.remove(&extension.field_number);
+        }
+        fields.add_length_delimited(extension.field_number, message.write_to_bytes()?);
+    }
+    Ok(())
+}
+
+/// Finds a player in a log, among the participants or, by name, the senders of chat messages.
+pub fn find_identity(log: &GameLog, query: &PlayerQuery) -> Option<Identity> {
+    let player = log
+        .get_teams()
+        .iter()
+        .flat_map(|t| t.get_players())
+        .find(|p| match query {
+            PlayerQuery::Uuid(uuid) => p.get_uuid() == uuid.as_slice(),
+            PlayerQuery::Name(name) => {
+                p.get_name().eq_ignore_ascii_case(name)
+                    || (p.has_nick() && p.get_nick().eq_ignore_ascii_case(name))
+            }
+        });
+    if let Some(player) = player {
+        let mut names = vec![player.get_name().to_string()];
+        if player.has_nick() && player.get_nick() != player.get_name() {
+            names.push(player.get_nick().into());
+        }
+        return Some(Identity {
+            names,
+            uuid: Some(player.get_uuid().to_vec()),
+        });
+    }
+    // Spectators only show up in the chat
+    let name = match query {
+        PlayerQuery::Name(name) => name,
+        PlayerQuery::Uuid(_) => return None,
+    };
+    log.get_events()
+        .iter()
+        .filter_map(|e| exts::chat.get(e.get_event()))
+        .find(|chat| chat.get_sender().eq_ignore_ascii_case(name))
+        .map(|chat| Identity {
+            names: vec![chat.get_sender().into()],
+            uuid: None,
+        })
+}
+
+/// Anonymizes a player in a log, returning the erased log and what was replaced.
+///
+/// Their name and nick are replaced with a placeholder and their UUID with a random one, in the
+/// fields that name players. Their chat messages are replaced with [`ERASED_MESSAGE`] and mentions
+/// of them in other messages with the placeholder. The placeholder differs between logs, so that
+/// the games of the player cannot be linked.
+pub fn erase_player(
+    mode: GameMode,
+    log: &GameLog,
+    identity: &Identity,
+) -> Result<(GameLog, Replacements)> {
+    let placeholder = format!("Anonymous-{:04x}", OsRng.next_u32() & 0xffff);
+    let mentions = Regex::new(&format!(
+        r"(?i)\b(?:{})\b",
+        identity
+            .names
+            .iter()
+            .map(|n| regex::escape(n))
+            .collect::<Vec<_>>()
+            .join("|")
+    ))
+    .unwrap();
+
+    let mut log = log.clone();
+    for event in log.mut_events().iter_mut().map(|e| e.mut_event()) {
+        rewrite_event(event, &exts::chat, |chat| {
+            let message = if identity
+                .names
+                .iter()
+                .any(|n| n.eq_ignore_ascii_case(chat.get_sender()))
+            {
+                ERASED_MESSAGE.into()
+            } else {
+                mentions
+                    .replace_all(chat.get_message(), placeholder.as_str())
+                    .into_owned()
+            };
+            chat.set_message(message);
+        })?;
+    }
+
+    let mut replacements = Replacements::default();
+    for name in &identity.names {
+        replacements.names.insert(name.clone(), placeholder.clone());
+    }
+    if let Some(uuid) = &identity.uuid {
+        let mut random = vec![0u8; 16];
+        OsRng.fill_bytes(&mut random);
+        replacements.uuids.insert(uuid.clone(), random);
+    }
+    Ok((rewrite_players(mode, &log, &replacements)?, replacements))
+}
+
+/// A stable UUID for a nick, so that nicked players cannot be told apart by it
+fn nick_uuid(nick: &str) -> Vec<u8> {
+    Sha256::digest(nick.as_bytes())[..16].to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protos::gamelog::{ChatEvent, ChatEvent_ChatType, Player, Team, TimeEvent};
+
+    fn chat(sender: &str, message: &str) -> TimeEvent {
+        let mut chat = ChatEvent::new();
+        chat.set_sender(sender.into());
+        chat.set_message(message.into());
+        chat.set_field_type(ChatEvent_ChatType::GLOBAL);
+        let mut event = TimeEvent::new();
+        event
+            .mut_event()
+            .mut_unknown_fields()
+            .add_length_delimited(exts::chat.field_number, chat.write_to_bytes().unwrap());
+        event
+    }
+
+    fn catch(leader: &str, carrier: &str) -> TimeEvent {
+        let mut catch = cai::CatchEvent::new();
+        catch.set_leader(leader.into());
+        catch.set_carrier(carrier.into());
+        let mut event = TimeEvent::new();
+        event.mut_event().mut_unknown_fields().add_length_delimited(
+            cai::exts::catch.field_number,
+            catch.write_to_bytes().unwrap(),
+        );
+        event
+    }
+
+    #[test]
+    fn player_query_key_matches_index_keys() {
+        assert_eq!(
+            PlayerQuery::parse("0102030405060708090A0B0C0D0E0F10").key(),
+            "01020304-0506-0708-090a-0b0c0d0e0f10"
+        );
+        assert_eq!(PlayerQuery::parse("Alice").key(), "alice");
+    }
+
+    #[test]
+    fn erase_player_keeps_team_and_map_names() {
+        let mut player = Player::new();
+        player.set_name("Red".into());
+        player.set_uuid(vec![1; 16]);
+        let mut team = Team::new();
+        team.set_name("Red".into());
+        team.mut_players().push(player);
+        let mut log = GameLog::new();
+        log.mut_teams().push(team);
+        log.set_winner("Red".into());
+        log.set_map("Red".into());
+        log.mut_events().push(chat("Red", "hi"));
+        log.mut_events().push(chat("bob", "gg red"));
+        log.mut_events().push(catch("Red", "bob"));
+
+        let identity = find_identity(&log, &PlayerQuery::Name("red".into())).unwrap();
+        let (erased, replacements) = erase_player(GameMode::CAI, &log, &identity).unwrap();
+
+        let player = &erased.get_teams()[0].get_players()[0];
+        assert!(player.get_name().starts_with("Anonymous-"));
+        assert_ne!(player.get_uuid(), &[1; 16]);
+        assert_eq!(erased.get_teams()[0].get_name(), "Red");
+        assert_eq!(erased.get_winner(), "Red");
+        assert_eq!(erased.get_map(), "Red");
+        assert_eq!(replacements.names["Red"], player.get_name());
+        assert_eq!(replacements.uuids[&vec![1; 16]], player.get_uuid());
+        assert_eq!(replacements.to_string(), "1 name and 1 UUID replaced");
+
+        let chats: Vec<ChatEvent> = erased
+            .get_events()
+            .iter()
+            .filter_map(|e| exts::chat.get(e.get_event()))
+            .collect();
+        assert_eq!(chats[0].get_sender(), player.get_name());
+        assert_eq!(chats[0].get_message(), ERASED_MESSAGE);
+        assert_eq!(chats[1].get_sender(), "bob");
+        assert_eq!(chats[1].get_message(), format!("gg {}", player.get_name()));
+
+        let catch = cai::exts::catch
+            .get(erased.get_events()[2].get_event())
+            .unwrap();
+        assert_eq!(catch.get_leader(), player.get_name());
+        assert_eq!(catch.get_carrier(), "bob");
+    }
+}
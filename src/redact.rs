@@ -0,0 +1,201 @@
+// Copyright (C) 2021 RoccoDev
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use regex::{Captures, Regex, RegexBuilder};
+use serde::Deserialize;
+use std::{
+    borrow::Cow,
+    sync::{Arc, RwLock},
+    time::SystemTime,
+};
+
+lazy_static::lazy_static! {
+    static ref IP_REGEX: Regex = Regex::new(r"\b(?:\d{1,3}\.){3}\d{1,3}(?::\d{1,5})?\b").unwrap();
+    static ref LINK_REGEX: Regex = Regex::new(
+        r"(?i)\b(?:https?://|www\.)\S+|\b[a-z0-9-]+(?:\.[a-z0-9-]+)*\.(?:com|net|org|gg|io|me|co|xyz|tk|ru|de|uk|us|tv)\b(?:/\S*)?"
+    )
+    .unwrap();
+}
+
+/// Chat redaction settings, read from the TOML file at `KIG_CHAT_FILTER` (`chat_filter.toml` by default)
+#[derive(Deserialize)]
+#[serde(default)]
+struct RedactionConfig {
+    /// Words masked with asterisks, matched case-insensitively
+    words: Vec<String>,
+    /// Regular expressions whose matches are masked with asterisks
+    patterns: Vec<String>,
+    /// Whether IP addresses are hidden
+    ip_addresses: bool,
+    /// Whether links are hidden
+    links: bool,
+    /// Domains whose links are kept, including their subdomains
+    allowed_domains: Vec<String>,
+}
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        RedactionConfig {
+            words: vec![],
+            patterns: vec![],
+            ip_addresses: true,
+            links: true,
+            allowed_domains: vec![],
+        }
+    }
+}
+
+/// Compiled redaction rules for chat messages shown publicly
+pub struct ChatRedactor {
+    words: Option<Regex>,
+    patterns: Vec<Regex>,
+    ip_addresses: bool,
+    links: bool,
+    allowed_domains: Vec<String>,
+}
+
+/// The redaction rules, reloaded when their file changes
+pub struct ChatRedaction {
+    path: String,
+    current: RwLock<(Option<SystemTime>, Arc<ChatRedactor>)>,
+}
+
+impl ChatRedactor {
+    fn new(config: RedactionConfig) -> Result<ChatRedactor, regex::Error> {
+        let words = if config.words.is_empty() {
+            None
+        } else {
+            let words: Vec<String> = config.words.iter().map(|w| regex::escape(w)).collect();
+            Some(
+                RegexBuilder::new(&format!(r"\b(?:{})\b", words.join("|")))
+                    .case_insensitive(true)
+                    .build()?,
+            )
+        };
+        Ok(ChatRedactor {
+            words,
+            patterns: config
+                .patterns
+                .iter()
+                .map(|p| Regex::new(p))
+                .collect::<Result<_, _>>()?,
+            ip_addresses: config.ip_addresses,
+            links: config.links,
+            allowed_domains: config
+                .allowed_domains
+                .iter()
+                .map(|d| d.to_lowercase())
+                .collect(),
+        })
+    }
+
+    /// Masks the parts of a chat message that should not be shown publicly
+    pub fn redact<'a>(&self, message: &'a str) -> Cow<'a, str> {
+        let mut message = Cow::Borrowed(message);
+        if self.ip_addresses {
+            replace(&mut message, &IP_REGEX, |_| "[IP]".into());
+        }
+        if self.links {
+            replace(&mut message, &LINK_REGEX, |link| {
+                if self.is_allowed(link) {
+                    link.into()
+                } else {
+                    "[link]".into()
+                }
+            });
+        }
+        if let Some(words) = &self.words {
+            replace(&mut message, words, mask);
+        }
+        for pattern in &self.patterns {
+            replace(&mut message, pattern, mask);
+        }
+        message
+    }
+
+    fn is_allowed(&self, link: &str) -> bool {
+        let link = link.to_lowercase();
+        let host = link
+            .trim_start_matches("https://")
+            .trim_start_matches("http://")
+            .split(['/', ':', '?', '#'])
+            .next()
+            .unwrap_or_default();
+        self.allowed_domains
+            .iter()
+            .any(|d| host == d || host.ends_with(&format!(".{}", d)))
+    }
+}
+
+impl ChatRedaction {
+    pub fn load() -> ChatRedaction {
+        let path =
+            std::env::var("KIG_CHAT_FILTER").unwrap_or_else(|_| String::from("chat_filter.toml"));
+        let redactor =
+            read(&path).unwrap_or_else(|e| panic!("invalid chat filter {}: {}", path, e));
+        ChatRedaction {
+            current: RwLock::new((modified(&path), Arc::new(redactor))),
+            path,
+        }
+    }
+
+    /// The current rules, reloaded first if their file was modified.
+    /// Invalid changes are reported, and the previous rules are kept.
+    pub fn rules(&self) -> Arc<ChatRedactor> {
+        let modified = modified(&self.path);
+        {
+            let current = self.current.read().unwrap();
+            if current.0 == modified {
+                return current.1.clone();
+            }
+        }
+        let mut current = self.current.write().unwrap();
+        if current.0 != modified {
+            match read(&self.path) {
+                Ok(redactor) => current.1 = Arc::new(redactor),
+                Err(e) => eprintln!(
+                    "Invalid chat filter {}, keeping the previous one: {}",
+                    self.path, e
+                ),
+            }
+            current.0 = modified;
+        }
+        current.1.clone()
+    }
+}
+
+fn read(path: &str) -> Result<ChatRedactor, String> {
+    let config = match std::fs::read_to_string(path) {
+        Ok(text) => toml::from_str(&text).map_err(|e| e.to_string())?,
+        Err(_) => RedactionConfig::default(),
+    };
+    ChatRedactor::new(config).map_err(|e| e.to_string())
+}
+
+fn modified(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn replace(message: &mut Cow<str>, regex: &Regex, replacement: impl Fn(&str) -> String) {
+    let replaced = match regex.replace_all(message, |c: &Captures| replacement(&c[0])) {
+        Cow::Owned(replaced) => replaced,
+        Cow::Borrowed(_) => return,
+    };
+    *message = Cow::Owned(replaced);
+}
+
+fn mask(text: &str) -> String {
+    "*".repeat(text.chars().count())
+}
@@ -0,0 +1,227 @@
+// Copyright (C) 2021 RoccoDev
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{
+    error::Result,
+    modes::GameMode,
+    web::{
+        gamelog::index::{self, IndexedGame},
+        get_current_year,
+        staff::Staff,
+    },
+    AppState,
+};
+use actix_web::{
+    http::header::{ContentType, IntoHeaderValue},
+    web, HttpResponse,
+};
+use askama::Template;
+use cached::proc_macro::once;
+use std::{collections::HashMap, time::Duration};
+
+/// Minimum amount of historical times before a stage can be checked
+const MIN_SAMPLES: usize = 20;
+/// Times below this fraction of the median are considered impossible
+const IMPOSSIBLE_RATIO: f64 = 0.25;
+/// Robust z-score (based on the median absolute deviation) above which a fast time is an outlier
+const OUTLIER_SCORE: f64 = 3.5;
+
+#[derive(Template)]
+#[template(path = "anomalies.html")]
+struct AnomaliesTemplate<'a> {
+    mode: GameMode,
+    games: &'a [FlaggedGame],
+    current_year: String,
+}
+
+#[derive(Clone)]
+pub struct FlaggedGame {
+    pub id: String,
+    pub start: i64,
+    pub server: Option<String>,
+    pub flags: Vec<Flag>,
+}
+
+#[derive(Clone)]
+pub struct Flag {
+    pub player: String,
+    pub check: String,
+    pub time: u64,
+    pub typical: u64,
+    pub severity: Severity,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Severity {
+    Impossible,
+    Outlier,
+}
+
+struct Distribution {
+    median: f64,
+    mad: f64,
+}
+
+pub async fn grav_anomalies(_staff: Staff, state: web::Data<AppState>) -> Result<HttpResponse> {
+    let games = grav_report(state).await?;
+    let render = AnomaliesTemplate {
+        mode: GameMode::GRAV,
+        games: &games,
+        current_year: get_current_year(),
+    }
+    .render()
+    .unwrap();
+    Ok(HttpResponse::Ok()
+        .content_type(IntoHeaderValue::try_into(ContentType::html()).unwrap())
+        .body(render))
+}
+
+/// Checks every public Gravity game against the per-stage statistics of all of them, using the
+/// stored game pages.
+#[once(time = 900, result = true, sync_writes = true)]
+async fn grav_report(state: web::Data<AppState>) -> Result<Vec<FlaggedGame>> {
+    let games = index::mode_games(state, GameMode::GRAV).await?;
+
+    // Stage times are grouped by stage name, as each game picks its own stages
+    let mut samples: HashMap<&str, Vec<f64>> = HashMap::new();
+    for stage in games.iter().flat_map(|g| &g.stages) {
+        samples
+            .entry(&stage.name)
+            .or_default()
+            .extend(stage.completions.iter().map(|(_, time)| *time as f64));
+    }
+    let stages: HashMap<&str, Distribution> = samples
+        .into_iter()
+        .filter_map(|(stage, times)| Some((stage, Distribution::new(times)?)))
+        .collect();
+
+    // Run times are compared as a ratio to the sum of the typical times of the game's stages
+    let expected_run = |game: &IndexedGame| -> Option<f64> {
+        game.stages
+            .iter()
+            .map(|s| stages.get(s.name.as_str()).map(|d| d.median))
+            .sum()
+    };
+    let run_ratios = Distribution::new(
+        games
+            .iter()
+            .filter_map(|g| Some((g, expected_run(g)?)))
+            .flat_map(|(g, expected)| {
+                full_runs(g)
+                    .into_iter()
+                    .map(move |(_, time)| time as f64 / expected)
+            })
+            .collect(),
+    );
+
+    let mut flagged = vec![];
+    for game in games.iter() {
+        let mut flags = vec![];
+        for (index, stage) in game.stages.iter().enumerate() {
+            let dist = match stages.get(stage.name.as_str()) {
+                Some(dist) => dist,
+                None => continue,
+            };
+            for (player, time) in &stage.completions {
+                if let Some(severity) = dist.check(*time as f64) {
+                    flags.push(Flag {
+                        player: player.clone(),
+                        check: format!("Stage {} ({})", index + 1, stage.name),
+                        time: *time,
+                        typical: dist.median as u64,
+                        severity,
+                    });
+                }
+            }
+        }
+        if let (Some(ratios), Some(expected)) = (&run_ratios, expected_run(game)) {
+            for (player, time) in full_runs(game) {
+                if let Some(severity) = ratios.check(time as f64 / expected) {
+                    flags.push(Flag {
+                        player: player.to_string(),
+                        check: "All stages".to_string(),
+                        time,
+                        typical: (expected * ratios.median) as u64,
+                        severity,
+                    });
+                }
+            }
+        }
+        if !flags.is_empty() {
+            flagged.push(FlaggedGame {
+                id: game.id.clone(),
+                start: game.start,
+                server: game.server.clone(),
+                flags,
+            });
+        }
+    }
+    flagged.sort_by_key(|g| std::cmp::Reverse(g.start));
+    Ok(flagged)
+}
+
+/// Total times of the players who completed every stage of a game without skipping any
+fn full_runs(game: &IndexedGame) -> Vec<(&str, u64)> {
+    let mut totals: HashMap<&str, (usize, u64)> = HashMap::new();
+    for (player, time) in game.stages.iter().flat_map(|s| &s.completions) {
+        let total = totals.entry(player).or_default();
+        total.0 += 1;
+        total.1 += time;
+    }
+    totals
+        .into_iter()
+        .filter(|(_, (stages, _))| *stages == game.stages.len())
+        .map(|(player, (_, time))| (player, time))
+        .collect()
+}
+
+impl Distribution {
+    fn new(mut values: Vec<f64>) -> Option<Self> {
+        if values.len() < MIN_SAMPLES {
+            return None;
+        }
+        let mid = median(&mut values);
+        let mut deviations: Vec<f64> = values.iter().map(|v| (v - mid).abs()).collect();
+        Some(Distribution {
+            median: mid,
+            mad: median(&mut deviations),
+        })
+    }
+
+    /// Only flags times that are faster than usual.
+    fn check(&self, value: f64) -> Option<Severity> {
+        if value < self.median * IMPOSSIBLE_RATIO {
+            Some(Severity::Impossible)
+        } else if self.mad > 0.0 && (self.median - value) / (1.4826 * self.mad) > OUTLIER_SCORE {
+            Some(Severity::Outlier)
+        } else {
+            None
+        }
+    }
+}
+
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let mid = values.len() / 2;
+    if values.len() % 2 == 1 {
+        values[mid]
+    } else {
+        (values[mid - 1] + values[mid]) / 2.0
+    }
+}
+
+mod filters {
+    pub use crate::web::gamelog::filters::*;
+}
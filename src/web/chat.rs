@@ -0,0 +1,123 @@
+// Copyright (C) 2021 RoccoDev
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{
+    auth::Role,
+    db::ChatFilter,
+    error::Result,
+    modes::GameMode,
+    web::{
+        gamelog::{encode_game_id, parse_mode},
+        get_current_year, non_empty,
+        staff::Staff,
+    },
+    AppState,
+};
+use actix_web::{
+    http::header::{ContentType, IntoHeaderValue},
+    web, HttpResponse,
+};
+use askama::Template;
+use serde::Deserialize;
+use strum::IntoEnumIterator;
+
+/// Maximum amount of messages shown for a search
+const MAX_MESSAGES: i64 = 200;
+
+#[derive(Template)]
+#[template(path = "chat_search.html")]
+struct ChatSearchTemplate<'a> {
+    query: &'a ChatSearchQuery,
+    modes: Vec<GameMode>,
+    messages: Vec<ChatMessage>,
+    current_year: String,
+}
+
+#[derive(Deserialize)]
+pub struct ChatSearchQuery {
+    #[serde(default)]
+    mode: String,
+    #[serde(default)]
+    phrase: String,
+    #[serde(default)]
+    sender: String,
+}
+
+struct ChatMessage {
+    mode: GameMode,
+    id: String,
+    start: i64,
+    event: u32,
+    time: i32,
+    sender: String,
+    message: String,
+    channel: String,
+}
+
+/// Staff search over the chat messages of every game
+pub async fn chat_search(
+    staff: Staff,
+    state: web::Data<AppState>,
+    query: web::Query<ChatSearchQuery>,
+) -> Result<HttpResponse> {
+    staff.require(Role::Moderator)?;
+    let filter = ChatFilter {
+        game: match non_empty(&query.mode) {
+            Some(mode) => Some(parse_mode(mode)?.get_database_id().into()),
+            None => None,
+        },
+        phrase: non_empty(&query.phrase),
+        sender: non_empty(&query.sender),
+    };
+    // Without filters, every message would match
+    let messages = if filter.phrase.is_none() && filter.sender.is_none() {
+        vec![]
+    } else {
+        state
+            .db
+            .search_chat(&filter, MAX_MESSAGES)
+            .await?
+            .into_iter()
+            .filter_map(|result| {
+                Some(ChatMessage {
+                    mode: parse_mode(result.game).ok()?,
+                    id: encode_game_id(&result.id),
+                    start: result.start,
+                    event: result.line.event,
+                    time: result.line.time,
+                    sender: result.line.sender,
+                    message: result.line.message,
+                    channel: result.line.channel,
+                })
+            })
+            .collect()
+    };
+
+    let render = ChatSearchTemplate {
+        query: &query,
+        modes: GameMode::iter().collect(),
+        messages,
+        current_year: get_current_year(),
+    }
+    .render()
+    .unwrap();
+    Ok(HttpResponse::Ok()
+        .content_type(IntoHeaderValue::try_into(ContentType::html()).unwrap())
+        .body(render))
+}
+
+mod filters {
+    pub use crate::web::gamelog::filters::*;
+}
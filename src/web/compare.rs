@@ -0,0 +1,273 @@
+// Copyright (C) 2021 RoccoDev
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{
+    error::{Error, Result},
+    modes::GameMode,
+    web::{
+        gamelog::{
+            avatar_url,
+            index::{self, IndexedGame},
+        },
+        get_current_year,
+    },
+    AppState,
+};
+use actix_web::{
+    http::header::{ContentType, IntoHeaderValue},
+    web, HttpResponse,
+};
+use askama::Template;
+use strum::IntoEnumIterator;
+
+const SHARED_GAMES: usize = 50;
+
+#[derive(Template)]
+#[template(path = "compare.html")]
+struct CompareTemplate {
+    a: Profile,
+    b: Profile,
+    record: Record,
+    games: Vec<SharedGame>,
+    total_games: usize,
+    modes: Vec<ModeComparison>,
+    current_year: String,
+}
+
+struct Profile {
+    uuid: String,
+    name: String,
+    avatar: String,
+}
+
+/// Results of the games the two players played together
+#[derive(Default)]
+struct Record {
+    teammates: u32,
+    won_together: u32,
+    opponents: u32,
+    a_wins: u32,
+    b_wins: u32,
+    a_killed_b: u32,
+    b_killed_a: u32,
+}
+
+struct SharedGame {
+    mode: GameMode,
+    id: String,
+    start: i64,
+    map: String,
+    same_team: bool,
+    a_won: bool,
+    b_won: bool,
+    a_killed_b: u32,
+    b_killed_a: u32,
+}
+
+struct ModeComparison {
+    mode: GameMode,
+    a: ModeStats,
+    b: ModeStats,
+}
+
+/// Statistics of a player over every game of a mode, not only the shared ones
+#[derive(Default)]
+struct ModeStats {
+    games: u32,
+    wins: u32,
+    kills: u32,
+    deaths: u32,
+}
+
+impl ModeStats {
+    fn win_rate(&self) -> String {
+        if self.games == 0 {
+            return String::from("-");
+        }
+        format!("{:.0}%", self.wins as f32 * 100.0 / self.games as f32)
+    }
+
+    fn add(&mut self, game: &IndexedGame, uuid: &str) {
+        let player = match game.player(uuid) {
+            Some(player) => player,
+            None => return,
+        };
+        self.games += 1;
+        self.wins += player.won as u32;
+        self.kills += game.kills.iter().filter(|k| k.killer == uuid).count() as u32;
+        self.deaths += game.kills.iter().filter(|k| k.victim == uuid).count() as u32;
+    }
+}
+
+pub async fn compare(
+    state: web::Data<AppState>,
+    web::Path((player_a, player_b)): web::Path<(String, String)>,
+) -> Result<HttpResponse> {
+    let a = find_profile(&state, &player_a).await?;
+    let b = find_profile(&state, &player_b).await?;
+
+    let mut record = Record::default();
+    let mut games = vec![];
+    let mut comparisons = vec![];
+    for mode in GameMode::iter() {
+        let mut comparison = ModeComparison {
+            mode,
+            a: ModeStats::default(),
+            b: ModeStats::default(),
+        };
+        let mode_games = state
+            .db
+            .public_games(mode, &[&a.uuid, &b.uuid], None)
+            .await?;
+        for game in &mode_games {
+            comparison.a.add(game, &a.uuid);
+            comparison.b.add(game, &b.uuid);
+
+            let (player_a, player_b) = match (game.player(&a.uuid), game.player(&b.uuid)) {
+                (Some(player_a), Some(player_b)) => (player_a, player_b),
+                _ => continue,
+            };
+            let kills = |killer: &str, victim: &str| {
+                game.kills
+                    .iter()
+                    .filter(|k| k.killer == killer && k.victim == victim)
+                    .count() as u32
+            };
+            let shared = SharedGame {
+                mode: game.mode,
+                id: game.id.clone(),
+                start: game.start,
+                map: game.map.clone(),
+                same_team: game.has_teams() && player_a.team == player_b.team,
+                a_won: player_a.won,
+                b_won: player_b.won,
+                a_killed_b: kills(&a.uuid, &b.uuid),
+                b_killed_a: kills(&b.uuid, &a.uuid),
+            };
+            if shared.same_team {
+                record.teammates += 1;
+                record.won_together += shared.a_won as u32;
+            } else {
+                record.opponents += 1;
+                record.a_wins += shared.a_won as u32;
+                record.b_wins += shared.b_won as u32;
+            }
+            record.a_killed_b += shared.a_killed_b;
+            record.b_killed_a += shared.b_killed_a;
+            games.push(shared);
+        }
+        if comparison.a.games > 0 || comparison.b.games > 0 {
+            comparisons.push(comparison);
+        }
+    }
+    games.sort_by_key(|g| std::cmp::Reverse(g.start));
+    let total_games = games.len();
+    games.truncate(SHARED_GAMES);
+
+    let render = CompareTemplate {
+        a,
+        b,
+        record,
+        games,
+        total_games,
+        modes: comparisons,
+        current_year: get_current_year(),
+    }
+    .render()
+    .unwrap();
+    Ok(HttpResponse::Ok()
+        .content_type(IntoHeaderValue::try_into(ContentType::html()).unwrap())
+        .body(render))
+}
+
+async fn find_profile(state: &AppState, name: &str) -> Result<Profile> {
+    index::find_player(&state.db, name)
+        .await?
+        .map(|player| Profile {
+            avatar: avatar_url(&player.uuid, &player.name, player.nicked),
+            uuid: player.uuid,
+            name: player.name,
+        })
+        .ok_or(Error::NotFound)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ModeStats;
+    use crate::{
+        modes::GameMode,
+        web::gamelog::index::{IndexedGame, IndexedKill, IndexedPlayer},
+    };
+
+    fn game(players: &[(&str, &str, bool)], kills: &[(&str, &str)]) -> IndexedGame {
+        IndexedGame {
+            mode: GameMode::CAI,
+            id: String::new(),
+            start: 0,
+            duration: 0,
+            map: String::new(),
+            description: String::new(),
+            winners: vec![],
+            players: players
+                .iter()
+                .map(|(uuid, team, won)| IndexedPlayer {
+                    uuid: uuid.to_string(),
+                    name: uuid.to_string(),
+                    nicked: false,
+                    team: team.to_string(),
+                    won: *won,
+                })
+                .collect(),
+            kills: kills
+                .iter()
+                .map(|(killer, victim)| IndexedKill {
+                    killer: killer.to_string(),
+                    victim: victim.to_string(),
+                })
+                .collect(),
+            stages: vec![],
+            server: None,
+        }
+    }
+
+    #[test]
+    fn mode_stats_count_the_games_of_the_player() {
+        let games = [
+            game(
+                &[("a", "Red", true), ("b", "Blue", false)],
+                &[("a", "b"), ("a", "b"), ("b", "a")],
+            ),
+            game(&[("a", "Red", false), ("c", "Red", false)], &[]),
+            game(&[("b", "Red", true), ("c", "Blue", false)], &[("b", "c")]),
+        ];
+        let mut stats = ModeStats::default();
+        for game in &games {
+            stats.add(game, "a");
+        }
+        assert_eq!(
+            (stats.games, stats.wins, stats.kills, stats.deaths),
+            (2, 1, 2, 1)
+        );
+        assert_eq!(stats.win_rate(), "50%");
+        assert_eq!(ModeStats::default().win_rate(), "-");
+    }
+
+    #[test]
+    fn has_teams_only_when_players_are_split() {
+        assert!(game(&[("a", "Red", true), ("b", "Blue", false)], &[]).has_teams());
+        assert!(!game(&[("a", "Players", true), ("b", "Players", false)], &[]).has_teams());
+        assert!(!game(&[], &[]).has_teams());
+    }
+}
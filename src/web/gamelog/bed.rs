@@ -1,5 +1,8 @@
 use crate::protos::gamelog::GameLog;
-use super::{event::EventType, GameLogExtension, WrappedExtension};
+use super::{
+    event::EventType, format_duration, recap, GameLogExtension, PlayerTeamMap, Team, WrappedEvent,
+    WrappedExtension,
+};
 use crate::protos::herd::exts::log_ext;
 use crate::protos::herd::{DeathEvent, DeathEvent_DeathCause};
 
@@ -18,6 +21,225 @@ impl BedExtension {
     pub fn is_respawn(&self) -> bool {
         self.respawn
     }
+
+    pub fn summary(
+        &self,
+        events: &[WrappedEvent],
+        teams: &[Team],
+        player_teams: &PlayerTeamMap,
+        winner: Option<&str>,
+    ) -> BedSummary {
+        let mut status: Vec<TeamStatus> = teams
+            .iter()
+            .map(|t| TeamStatus {
+                name: t.name.to_string(),
+                color: t.color,
+                alive: t.players.len(),
+                bed: None,
+                eliminated: None,
+                final_kills: 0,
+                place: 0,
+            })
+            .collect();
+        let mut steps = vec![];
+        let mut final_killers: Vec<FinalKiller> = vec![];
+        let mut eliminations = 0;
+
+        let team_idx = |player: &str, id: usize| {
+            player_teams
+                .get_team_at(player, id)
+                .and_then(|t| teams.iter().position(|o| o.name == t.name))
+        };
+        for event in events {
+            match &event.event {
+                EventType::HerdDeath(death) => {
+                    // Deaths with a respawn do not eliminate the player
+                    if death.get_respawn() {
+                        continue;
+                    }
+                    if let Some(team) = team_idx(death.get_player(), event.id) {
+                        status[team].alive = status[team].alive.saturating_sub(1);
+                    }
+                    if !self.respawn || !death.has_killer() {
+                        continue;
+                    }
+                    if let Some(team) = team_idx(death.get_killer(), event.id) {
+                        status[team].final_kills += 1;
+                    }
+                    match final_killers
+                        .iter_mut()
+                        .find(|k| k.name == death.get_killer())
+                    {
+                        Some(killer) => killer.kills += 1,
+                        None => final_killers.push(FinalKiller {
+                            name: death.get_killer().to_string(),
+                            color: player_teams
+                                .get_team_at(death.get_killer(), event.id)
+                                .map(|t| t.color)
+                                .unwrap_or(""),
+                            kills: 1,
+                        }),
+                    }
+                }
+                EventType::BedBedDestruction(bed) => {
+                    let team = bed.get_team() as usize;
+                    if let Some(team) = status.get_mut(team) {
+                        team.bed = Some(BedLoss {
+                            time: event.time,
+                            player: bed.has_player().then(|| bed.get_player().to_string()),
+                            alive: team.alive,
+                        });
+                    }
+                    steps.push(StatusStep::new(
+                        event.time,
+                        team,
+                        StepKind::BedDestroyed,
+                        &status,
+                    ));
+                }
+                EventType::HerdElimination(elimination) => {
+                    let team = elimination.get_team() as usize;
+                    eliminations += 1;
+                    if let Some(team) = status.get_mut(team) {
+                        team.alive = 0;
+                        team.eliminated = Some(Elimination {
+                            time: event.time,
+                            order: eliminations,
+                        });
+                    }
+                    steps.push(StatusStep::new(
+                        event.time,
+                        team,
+                        StepKind::Eliminated,
+                        &status,
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        // Winner first, then surviving teams, then eliminated teams from last to first
+        let rank = |t: &TeamStatus| {
+            if Some(t.name.as_str()) == winner {
+                0
+            } else {
+                t.eliminated
+                    .as_ref()
+                    .map(|e| usize::MAX - e.order)
+                    .unwrap_or(1)
+            }
+        };
+        let ranks: Vec<usize> = status.iter().map(rank).collect();
+        for (team, rank) in status.iter_mut().zip(&ranks) {
+            team.place = 1 + ranks.iter().filter(|r| *r < rank).count();
+        }
+        final_killers.sort_by_key(|k| std::cmp::Reverse(k.kills));
+
+        BedSummary {
+            teams: status,
+            steps,
+            final_killers,
+        }
+    }
+
+    /// Narrates the first bed loss, the top final killer and the last elimination
+    pub fn recap(&self, summary: &BedSummary) -> Vec<String> {
+        let mut lines = vec![];
+        if let Some((team, bed)) = summary
+            .teams
+            .iter()
+            .filter_map(|t| t.bed.as_ref().map(|bed| (t, bed)))
+            .min_by_key(|(_, bed)| bed.time)
+        {
+            lines.push(format!(
+                "{} lost their bed first at {}",
+                team.name,
+                format_duration(bed.time)
+            ));
+        }
+        lines.extend(recap::top_clause(
+            summary
+                .final_killers
+                .iter()
+                .map(|k| (k.name.as_str(), k.kills)),
+            |n| format!("got {}", recap::plural(n, "final kill")),
+        ));
+        if let Some(last) = summary
+            .steps
+            .iter()
+            .rev()
+            .find(|s| s.kind == StepKind::Eliminated)
+        {
+            lines.push(recap::decided_at(last.time));
+        }
+        lines
+    }
+}
+
+/// Team status board, tracking beds and eliminations
+pub struct BedSummary {
+    pub teams: Vec<TeamStatus>,
+    pub steps: Vec<StatusStep>,
+    pub final_killers: Vec<FinalKiller>,
+}
+
+pub struct TeamStatus {
+    pub name: String,
+    pub color: &'static str,
+    pub alive: usize,
+    pub bed: Option<BedLoss>,
+    pub eliminated: Option<Elimination>,
+    pub final_kills: u32,
+    pub place: usize,
+}
+
+pub struct BedLoss {
+    pub time: i32,
+    pub player: Option<String>,
+    /// Players left in the team when the bed was destroyed
+    pub alive: usize,
+}
+
+pub struct Elimination {
+    pub time: i32,
+    pub order: usize,
+}
+
+pub struct StatusStep {
+    pub time: i32,
+    pub team: String,
+    pub color: &'static str,
+    pub kind: StepKind,
+    /// Players left in each team, with the team's color
+    pub alive: Vec<(usize, &'static str)>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum StepKind {
+    BedDestroyed,
+    Eliminated,
+}
+
+pub struct FinalKiller {
+    pub name: String,
+    pub color: &'static str,
+    pub kills: u32,
+}
+
+impl StatusStep {
+    fn new(time: i32, team: usize, kind: StepKind, status: &[TeamStatus]) -> Self {
+        let (name, color) = status
+            .get(team)
+            .map(|t| (t.name.clone(), t.color))
+            .unwrap_or_default();
+        StatusStep {
+            time,
+            team: name,
+            color,
+            kind,
+            alive: status.iter().map(|t| (t.alive, t.color)).collect(),
+        }
+    }
 }
 
 impl WrappedExtension {
@@ -1,10 +1,173 @@
 use crate::protos::bp::{DeathEvent_PlayerDeathEvent, DeathEvent_PlayerDeathEvent_DeathCause};
 
-use super::{event::EventType, GameLogExtension};
+use super::{event::EventType, recap, GameLogExtension, Team, WrappedEvent};
 
 #[derive(Clone, Copy)]
 pub struct BpExtension {}
 
+/// Round-by-round eliminations and final placements
+pub struct BpSummary {
+    pub rounds: Vec<Round>,
+    pub winners: Vec<String>,
+    pub placements: Vec<Placement>,
+}
+
+pub struct Round {
+    pub number: usize,
+    pub floor: String,
+    pub time: i32,
+    /// Players alive when the round started
+    pub survivors: usize,
+    pub eliminated: Vec<Elimination>,
+    pub powerups: Vec<PowerUp>,
+}
+
+pub struct Elimination {
+    pub player: String,
+    pub cause: &'static str,
+}
+
+pub struct PowerUp {
+    pub player: String,
+    pub power_up: String,
+}
+
+pub struct Placement {
+    pub place: usize,
+    pub players: Vec<String>,
+}
+
+impl BpExtension {
+    pub fn summary(&self, events: &[WrappedEvent], teams: &[Team]) -> BpSummary {
+        let mut rounds: Vec<Round> = vec![];
+        let mut winners = vec![];
+        // Players eliminated together, in elimination order
+        let mut batches: Vec<Vec<String>> = vec![];
+
+        for event in events {
+            match &event.event {
+                EventType::BpRound(round) => rounds.push(Round {
+                    number: rounds.len() + 1,
+                    floor: round.get_floor().to_string(),
+                    time: event.time,
+                    survivors: 0,
+                    eliminated: vec![],
+                    powerups: vec![],
+                }),
+                EventType::BpDeath(death) => {
+                    let round = current_round(&mut rounds, event.time);
+                    round
+                        .eliminated
+                        .extend(death.get_player().iter().map(|p| Elimination {
+                            player: p.get_name().to_string(),
+                            cause: p.get_damage_desc(),
+                        }));
+                    batches.push(
+                        death
+                            .get_player()
+                            .iter()
+                            .map(|p| p.get_name().to_string())
+                            .collect(),
+                    );
+                }
+                EventType::BpPowerup(powerup) => current_round(&mut rounds, event.time)
+                    .powerups
+                    .push(PowerUp {
+                        player: powerup.get_name().to_string(),
+                        power_up: powerup.get_powerUp().to_string(),
+                    }),
+                EventType::BpWinners(event) => winners = event.get_winner().to_vec(),
+                _ => {}
+            }
+        }
+
+        let eliminated: usize = batches.iter().map(Vec::len).sum();
+        let mut alive = teams
+            .iter()
+            .map(|t| t.players.len())
+            .sum::<usize>()
+            .max(eliminated + winners.len());
+        for round in &mut rounds {
+            round.survivors = alive;
+            alive = alive.saturating_sub(round.eliminated.len());
+        }
+
+        let mut placements = vec![];
+        if !winners.is_empty() {
+            placements.push(Placement {
+                place: 1,
+                players: winners.clone(),
+            });
+        }
+        let mut place = winners.len() + 1;
+        for batch in batches.into_iter().rev() {
+            let players = batch.len();
+            placements.push(Placement {
+                place,
+                players: batch,
+            });
+            place += players;
+        }
+
+        BpSummary {
+            rounds,
+            winners,
+            placements,
+        }
+    }
+
+    /// Narrates the deadliest round and power-up pickups
+    pub fn recap(&self, summary: &BpSummary) -> Vec<String> {
+        let mut lines = vec![];
+        if let Some(round) = summary
+            .rounds
+            .iter()
+            .filter(|r| r.eliminated.len() > 1)
+            .max_by_key(|r| (r.eliminated.len(), std::cmp::Reverse(r.number)))
+        {
+            lines.push(format!(
+                "round {}{} eliminated {} players",
+                round.number,
+                if round.floor.is_empty() {
+                    String::new()
+                } else {
+                    format!(" on {}", round.floor)
+                },
+                round.eliminated.len()
+            ));
+        }
+        let mut powerups: Vec<(&str, u32)> = vec![];
+        for powerup in summary.rounds.iter().flat_map(|r| r.powerups.iter()) {
+            match powerups
+                .iter_mut()
+                .find(|(name, _)| *name == powerup.player)
+            {
+                Some((_, count)) => *count += 1,
+                None => powerups.push((&powerup.player, 1)),
+            }
+        }
+        lines.extend(recap::top_clause(powerups, |n| {
+            format!("picked up {}", recap::plural(n, "power-up"))
+        }));
+        lines
+    }
+}
+
+/// Events before the first round are grouped into a round without a floor
+fn current_round(rounds: &mut Vec<Round>, time: i32) -> &mut Round {
+    if rounds.is_empty() {
+        rounds.push(Round {
+            number: 0,
+            floor: String::new(),
+            time,
+            survivors: 0,
+            eliminated: vec![],
+            powerups: vec![],
+        });
+    }
+    rounds.last_mut().unwrap()
+}
+
 impl GameLogExtension for BpExtension {
     fn get_box_color(&self, event: &super::EventType) -> &'static str {
         match event {
@@ -1,10 +1,221 @@
 use crate::protos::{self, gamelog::GameEvent};
 
-use super::{event::EventType, GameLogExtension};
+use super::{
+    event::EventType, recap, GameLogExtension, PlayerTeamMap, Team, WrappedEvent, SPECTATORS,
+};
 
 #[derive(Clone, Copy)]
 pub struct CaiExtension {}
 
+/// Leader protection and capture overview
+pub struct CaiSummary {
+    pub leaders: Vec<LeaderStats>,
+    pub players: Vec<Contribution>,
+    pub progression: Vec<ScoreStep>,
+}
+
+pub struct LeaderStats {
+    pub name: String,
+    pub color: &'static str,
+    pub caught: u32,
+    pub captured: u32,
+    pub saves: u32,
+    /// Chances of the escapes that were rolled, in percent
+    pub chances: Vec<f32>,
+    pub carriers: Vec<Carrier>,
+}
+
+pub struct Carrier {
+    pub name: String,
+    pub catches: u32,
+}
+
+pub struct Contribution {
+    pub name: String,
+    pub color: &'static str,
+    pub catches: u32,
+    pub captures: u32,
+    pub saves: u32,
+}
+
+pub struct ScoreStep {
+    pub time: i32,
+    pub carrier: String,
+    pub leader: String,
+    pub color: &'static str,
+    /// Score and color of each team that has captured so far
+    pub scores: Vec<(i32, &'static str)>,
+}
+
+impl CaiExtension {
+    pub fn summary(&self, events: &[WrappedEvent], player_teams: &PlayerTeamMap) -> CaiSummary {
+        let mut leaders: Vec<LeaderStats> = vec![];
+        let mut players: Vec<Contribution> = vec![];
+        let mut teams: Vec<&str> = vec![];
+        let mut progression: Vec<ScoreStep> = vec![];
+        let mut scores: Vec<(i32, &'static str)> = vec![];
+
+        let team_at = |player: &str, id: usize| -> &Team {
+            player_teams.get_team_at(player, id).unwrap_or(&SPECTATORS)
+        };
+        for event in events {
+            match &event.event {
+                EventType::CaiCatch(catch) => {
+                    let color = team_at(catch.get_leader(), event.id).color;
+                    let leader = leader_entry(&mut leaders, catch.get_leader(), color);
+                    leader.caught += 1;
+                    match leader
+                        .carriers
+                        .iter_mut()
+                        .find(|c| c.name == catch.get_carrier())
+                    {
+                        Some(carrier) => carrier.catches += 1,
+                        None => leader.carriers.push(Carrier {
+                            name: catch.get_carrier().to_string(),
+                            catches: 1,
+                        }),
+                    }
+                    let color = team_at(catch.get_carrier(), event.id).color;
+                    contribution_entry(&mut players, catch.get_carrier(), color).catches += 1;
+                }
+                EventType::CaiEscape(escape) => {
+                    let color = team_at(escape.get_leader(), event.id).color;
+                    let leader = leader_entry(&mut leaders, escape.get_leader(), color);
+                    if escape.has_saver() {
+                        leader.saves += 1;
+                        let color = team_at(escape.get_saver(), event.id).color;
+                        contribution_entry(&mut players, escape.get_saver(), color).saves += 1;
+                    } else if escape.has_chance() {
+                        leader.chances.push(escape.get_chance());
+                    }
+                }
+                EventType::CaiCapture(capture) => {
+                    let color = team_at(capture.get_leader(), event.id).color;
+                    leader_entry(&mut leaders, capture.get_leader(), color).captured += 1;
+                    let team = team_at(capture.get_carrier(), event.id);
+                    contribution_entry(&mut players, capture.get_carrier(), team.color).captures +=
+                        1;
+
+                    let idx = match teams.iter().position(|name| *name == team.name) {
+                        Some(idx) => idx,
+                        None => {
+                            teams.push(team.name);
+                            scores.push((0, team.color));
+                            teams.len() - 1
+                        }
+                    };
+                    scores[idx].0 += 1;
+                    progression.push(ScoreStep {
+                        time: event.time,
+                        carrier: capture.get_carrier().to_string(),
+                        leader: capture.get_leader().to_string(),
+                        color: team.color,
+                        scores: scores.clone(),
+                    });
+                }
+                _ => {}
+            }
+        }
+        players.sort_by_key(|p| std::cmp::Reverse((p.captures, p.catches, p.saves)));
+        CaiSummary {
+            leaders,
+            players,
+            progression,
+        }
+    }
+
+    /// Narrates captures and the players who caught, captured and saved the most
+    pub fn recap(&self, summary: &CaiSummary, teams: &[Team]) -> Vec<String> {
+        let team_name = |color: &str| teams.iter().find(|t| t.color == color).map(|t| t.name);
+        let mut lines = vec![];
+        for leader in summary.leaders.iter().filter(|l| l.captured > 0) {
+            let capturers = summary
+                .progression
+                .iter()
+                .find(|s| s.leader == leader.name)
+                .and_then(|s| team_name(s.color));
+            let captured = team_name(leader.color)
+                .map(|team| format!("{} leader", recap::possessive(team)))
+                .unwrap_or_else(|| leader.name.clone());
+            lines.push(match capturers {
+                Some(team) => format!(
+                    "{} captured {} {}",
+                    team,
+                    captured,
+                    recap::times(leader.captured)
+                ),
+                None => format!(
+                    "{} was captured {}",
+                    captured,
+                    recap::times(leader.captured)
+                ),
+            });
+        }
+        let players = || summary.players.iter();
+        lines.extend(recap::top_clause(
+            players().map(|p| (p.name.as_str(), p.catches)),
+            |n| format!("caught a leader {}", recap::times(n)),
+        ));
+        lines.extend(recap::top_clause(
+            players().map(|p| (p.name.as_str(), p.saves)),
+            |n| format!("made {}", recap::plural(n, "save")),
+        ));
+        if let Some(last) = summary.progression.last() {
+            lines.push(recap::decided_at(last.time));
+        }
+        lines
+    }
+}
+
+impl LeaderStats {
+    pub fn average_chance(&self) -> Option<f32> {
+        (!self.chances.is_empty())
+            .then(|| self.chances.iter().sum::<f32>() / self.chances.len() as f32)
+    }
+}
+
+fn leader_entry<'a>(
+    leaders: &'a mut Vec<LeaderStats>,
+    name: &str,
+    color: &'static str,
+) -> &'a mut LeaderStats {
+    match leaders.iter().position(|l| l.name == name) {
+        Some(idx) => &mut leaders[idx],
+        None => {
+            leaders.push(LeaderStats {
+                name: name.to_string(),
+                color,
+                caught: 0,
+                captured: 0,
+                saves: 0,
+                chances: vec![],
+                carriers: vec![],
+            });
+            leaders.last_mut().unwrap()
+        }
+    }
+}
+
+fn contribution_entry<'a>(
+    players: &'a mut Vec<Contribution>,
+    name: &str,
+    color: &'static str,
+) -> &'a mut Contribution {
+    match players.iter().position(|p| p.name == name) {
+        Some(idx) => &mut players[idx],
+        None => {
+            players.push(Contribution {
+                name: name.to_string(),
+                color,
+                catches: 0,
+                captures: 0,
+                saves: 0,
+            });
+            players.last_mut().unwrap()
+        }
+    }
+}
+
 impl GameLogExtension for CaiExtension {
     fn get_box_color(&self, event: &super::EventType) -> &'static str {
         match event {
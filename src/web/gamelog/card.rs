@@ -0,0 +1,282 @@
+use super::{
+    analyze_log,
+    chart::xml_escape,
+    filters, get_log, get_teams, get_winners, map_entries, map_names, parse_game_path,
+    recap::{self, plural},
+    AnalyzedLog, PlayerTeamMap, Summary, WrappedEvent,
+};
+use crate::{error::Result, modes::GameMode, AppState};
+use actix_web::{http::header, web, HttpResponse};
+use cached::{proc_macro::cached, Cached, TimedSizedCache};
+use resvg::{tiny_skia, usvg};
+use std::{fmt::Write, path::Path, sync::Arc, time::Duration};
+
+const WIDTH: u32 = 1200;
+const HEIGHT: u32 = 630;
+const TOP_PLAYERS: usize = 5;
+const FONT_FAMILY: &str = "'DejaVu Sans', Arial, sans-serif";
+/// Rendered cards kept in memory, the least recently used ones are dropped first
+const CACHED_CARDS: usize = 500;
+
+lazy_static::lazy_static! {
+    /// System fonts, plus the ones in `KIG_FONTS_DIR` if set
+    static ref FONTS: Arc<usvg::fontdb::Database> = {
+        let mut db = usvg::fontdb::Database::new();
+        db.load_system_fonts();
+        if let Ok(dir) = std::env::var("KIG_FONTS_DIR") {
+            db.load_fonts_dir(dir);
+        }
+        Arc::new(db)
+    };
+}
+
+/// A player highlighted on the card, e.g. the top killer
+struct TopPlayer {
+    name: String,
+    detail: String,
+    color: String,
+}
+
+pub async fn card_png(
+    state: web::Data<AppState>,
+    web::Path((mode, path_id)): web::Path<(String, String)>,
+) -> Result<HttpResponse> {
+    let (mode, id) = parse_game_path(mode, &path_id)?;
+    let png = get_card(state, mode, id, path_id).await?;
+    Ok(HttpResponse::Ok()
+        .content_type("image/png")
+        .header(header::CACHE_CONTROL, "public, max-age=3600")
+        .body(png))
+}
+
+/// Renders the card of a game. Only cards of existing games are cached, as errors are not.
+#[cached(
+    ty = "TimedSizedCache<(Vec<u8>, GameMode), Vec<u8>>",
+    create = "{ TimedSizedCache::with_size_and_lifespan(CACHED_CARDS, Duration::from_secs(3600)) }",
+    convert = "{ (id.clone(), mode) }",
+    result
+)]
+async fn get_card(
+    state: web::Data<AppState>,
+    mode: GameMode,
+    id: Vec<u8>,
+    game_id: String,
+) -> Result<Vec<u8>> {
+    let (log, _) = get_log(state.clone(), mode, id, false).await?;
+    let teams = get_teams(&log);
+    let AnalyzedLog {
+        winner,
+        extension_ptr,
+        events,
+        player_teams,
+        summary,
+        duration,
+        ..
+    } = analyze_log(mode, &log, &teams);
+
+    let maps = map_entries(&state.maps, mode, &log, &*extension_ptr, &summary);
+    let winners: Vec<(String, String)> = get_winners(&summary, winner.as_ref(), &player_teams)
+        .into_iter()
+        .map(|(name, color)| (name.to_string(), team_color(color).to_string()))
+        .collect();
+    let svg = card_svg(
+        mode,
+        &game_id,
+        &map_names(&maps),
+        maps.first().map_or("", |m| m.image.as_str()),
+        &winners,
+        super::format_duration(duration),
+        &top_players(&summary, &events, &player_teams),
+    );
+    web::block(move || rasterize(&svg))
+        .await
+        .map_err(|_| crate::error::Error::Render)
+}
+
+/// Drops the cached card of a game
+pub(super) async fn forget_card(mode: GameMode, id: &[u8]) {
+    GET_CARD.lock().await.cache_remove(&(id.to_vec(), mode));
+}
+
+fn card_svg(
+    mode: GameMode,
+    game_id: &str,
+    map: &str,
+    image: &str,
+    winners: &[(String, String)],
+    duration: String,
+    top_players: &[TopPlayer],
+) -> String {
+    let mut svg = String::new();
+    let _ = write!(
+        svg,
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="{font}"><rect width="{w}" height="{h}" fill="#1d1d24"/>"##,
+        w = WIDTH,
+        h = HEIGHT,
+        font = FONT_FAMILY,
+    );
+
+    // Map screenshot as the background, if there is one
+    if !image.is_empty() && Path::new("img").join(image).exists() {
+        let _ = write!(
+            svg,
+            r#"<image href="{href}" width="{w}" height="{h}" preserveAspectRatio="xMidYMid slice"/>"#,
+            href = xml_escape(image),
+            w = WIDTH,
+            h = HEIGHT,
+        );
+    }
+    let _ = write!(
+        svg,
+        r##"<defs><linearGradient id="shade" x1="0" x2="1" y1="0" y2="0"><stop offset="0" stop-color="#101014" stop-opacity="0.95"/><stop offset="1" stop-color="#101014" stop-opacity="0.7"/></linearGradient></defs><rect width="{w}" height="{h}" fill="url(#shade)"/>"##,
+        w = WIDTH,
+        h = HEIGHT,
+    );
+
+    let _ = write!(
+        svg,
+        r##"<text x="64" y="92" font-size="30" font-weight="bold" fill="#f7b32a">KIG Network</text><text x="64" y="170" font-size="58" font-weight="bold" fill="#ffffff">{mode}</text><text x="64" y="222" font-size="30" fill="#c8c8d0">Game {id}{map}</text>"##,
+        mode = xml_escape(mode.get_full_name()),
+        id = xml_escape(game_id),
+        map = if map.is_empty() {
+            String::new()
+        } else {
+            format!(" · {}", xml_escape(map))
+        },
+    );
+
+    let _ = write!(
+        svg,
+        r##"<text x="64" y="340" font-size="30" fill="#c8c8d0">Winner{plural}</text><text x="64" y="400" font-size="50" font-weight="bold">"##,
+        plural = if winners.len() > 1 { "s" } else { "" },
+    );
+    if winners.is_empty() {
+        svg.push_str(r##"<tspan fill="#ffffff">Tie</tspan>"##);
+    }
+    for (i, (name, color)) in winners.iter().enumerate() {
+        let _ = write!(
+            svg,
+            r#"<tspan fill="{color}">{name}</tspan>{sep}"#,
+            color = color,
+            name = xml_escape(name),
+            sep = if i + 1 < winners.len() {
+                r##"<tspan fill="#ffffff">, </tspan>"##
+            } else {
+                ""
+            },
+        );
+    }
+    svg.push_str("</text>");
+
+    let _ = write!(
+        svg,
+        r##"<text x="64" y="560" font-size="30" fill="#c8c8d0">Duration <tspan font-weight="bold" fill="#ffffff">{}</tspan></text>"##,
+        duration
+    );
+
+    if !top_players.is_empty() {
+        svg.push_str(r##"<text x="760" y="340" font-size="30" fill="#c8c8d0">Top players</text>"##);
+    }
+    for (i, player) in top_players.iter().enumerate() {
+        let y = 392 + i as u32 * 44;
+        let _ = write!(
+            svg,
+            r##"<text x="760" y="{y}" font-size="32" font-weight="bold" fill="{color}">{name}</text><text x="1136" y="{y}" font-size="28" text-anchor="end" fill="#c8c8d0">{detail}</text>"##,
+            y = y,
+            color = &player.color,
+            name = xml_escape(&player.name),
+            detail = xml_escape(&player.detail),
+        );
+    }
+
+    svg.push_str("</svg>");
+    svg
+}
+
+/// Picks the players to highlight: finishers in Gravity, placements in BlockParty,
+/// captures in Cowboys and Indians and kills in the other modes.
+fn top_players(
+    summary: &Summary,
+    events: &[WrappedEvent],
+    player_teams: &PlayerTeamMap,
+) -> Vec<TopPlayer> {
+    let color = |player: &str| {
+        team_color(
+            player_teams
+                .get_team_at(player, usize::MAX)
+                .map(|t| t.color)
+                .unwrap_or(""),
+        )
+        .to_string()
+    };
+    let mut players: Vec<TopPlayer> = match summary {
+        Summary::Grav(splits) => splits
+            .rows
+            .iter()
+            .filter_map(|row| {
+                let finish = row.finish?;
+                Some(TopPlayer {
+                    name: row.player.clone(),
+                    detail: format!(
+                        "#{} · {}",
+                        finish.place,
+                        filters::grav_format_time(&finish.time).unwrap_or_default()
+                    ),
+                    color: color(&row.player),
+                })
+            })
+            .collect(),
+        Summary::Bp(bp) => bp
+            .placements
+            .iter()
+            .flat_map(|p| {
+                p.players.iter().map(move |name| TopPlayer {
+                    name: name.clone(),
+                    detail: format!("#{}", p.place),
+                    color: color(name),
+                })
+            })
+            .collect(),
+        Summary::Cai(cai) => cai
+            .players
+            .iter()
+            .filter(|p| p.captures > 0)
+            .map(|p| TopPlayer {
+                name: p.name.clone(),
+                detail: plural(p.captures, "capture"),
+                color: team_color(p.color).to_string(),
+            })
+            .collect(),
+        _ => recap::kill_counts(events)
+            .into_iter()
+            .map(|(name, count)| TopPlayer {
+                name: name.to_string(),
+                detail: plural(count, "kill"),
+                color: color(name),
+            })
+            .collect(),
+    };
+    players.truncate(TOP_PLAYERS);
+    players
+}
+
+fn rasterize(svg: &str) -> std::result::Result<Vec<u8>, String> {
+    let options = usvg::Options {
+        resources_dir: Some("img".into()),
+        fontdb: FONTS.clone(),
+        ..Default::default()
+    };
+    let tree = usvg::Tree::from_str(svg, &options).map_err(|e| e.to_string())?;
+    let mut pixmap = tiny_skia::Pixmap::new(WIDTH, HEIGHT).ok_or("invalid card size")?;
+    resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+    pixmap.encode_png().map_err(|e| e.to_string())
+}
+
+/// Team colors are tuned for a light background, unknown ones are drawn in white
+fn team_color(color: &str) -> &str {
+    if color.is_empty() {
+        "#ffffff"
+    } else {
+        color
+    }
+}
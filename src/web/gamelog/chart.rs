@@ -0,0 +1,277 @@
+use super::{
+    analyze_log, event::EventType, get_log, get_teams, parse_game_path, AnalyzedLog, PlayerTeamMap,
+    Team, WrappedEvent, WrappedExtension,
+};
+use crate::{
+    error::{Error, Result},
+    AppState,
+};
+use actix_web::{web, HttpResponse};
+use std::fmt::Write;
+
+const WIDTH: f64 = 800.0;
+const HEIGHT: f64 = 260.0;
+const MARGIN_LEFT: f64 = 40.0;
+const MARGIN_RIGHT: f64 = 16.0;
+const MARGIN_TOP: f64 = 16.0;
+const MARGIN_BOTTOM: f64 = 56.0;
+/// Candidate spacings of the time axis labels, in seconds
+const TIME_STEPS: [i32; 8] = [15, 30, 60, 120, 300, 600, 900, 1800];
+const MAX_TIME_TICKS: i32 = 8;
+
+/// A step chart of a value over the game time, e.g. the score of each team
+pub struct Chart {
+    pub title: &'static str,
+    /// Game time at the end of the chart, in milliseconds
+    pub end: i32,
+    pub series: Vec<Series>,
+}
+
+pub struct Series {
+    pub name: String,
+    pub color: &'static str,
+    pub initial: i32,
+    /// Game time and new value of each change
+    pub steps: Vec<(i32, i32)>,
+}
+
+pub async fn chart_svg(
+    state: web::Data<AppState>,
+    web::Path((mode, path_id)): web::Path<(String, String)>,
+) -> Result<HttpResponse> {
+    let (mode, id) = parse_game_path(mode, &path_id)?;
+    let (log, _) = get_log(state, mode, id, false).await?;
+    let teams = get_teams(&log);
+    let AnalyzedLog {
+        extension,
+        events,
+        player_teams,
+        duration,
+        ..
+    } = analyze_log(mode, &log, &teams);
+    let chart = extension
+        .chart(&events, &teams, &player_teams, duration)
+        .ok_or(Error::NotFound)?;
+    Ok(HttpResponse::Ok()
+        .content_type("image/svg+xml")
+        .body(chart.to_svg()))
+}
+
+impl WrappedExtension {
+    pub(super) fn chart(
+        &self,
+        events: &[WrappedEvent],
+        teams: &[Team],
+        player_teams: &PlayerTeamMap,
+        end: i32,
+    ) -> Option<Chart> {
+        let (title, series) = match self {
+            WrappedExtension::Cai(_) => ("Score", team_scores(events, teams, player_teams)),
+            WrappedExtension::Timv(_)
+            | WrappedExtension::Bed(_)
+            | WrappedExtension::Halloween(_) => {
+                ("Players alive", players_alive(events, teams, player_teams))
+            }
+            WrappedExtension::Bp(_) => ("Survivors", survivors(events, teams)),
+            WrappedExtension::Grav(_) => return None,
+        };
+        (!series.is_empty()).then_some(Chart { title, end, series })
+    }
+}
+
+fn team_series(teams: &[Team], initial: impl Fn(&Team) -> i32) -> Vec<Series> {
+    teams
+        .iter()
+        .map(|t| Series {
+            name: t.name.to_string(),
+            color: t.color,
+            initial: initial(t),
+            steps: vec![],
+        })
+        .collect()
+}
+
+/// Adds `delta` to the value of the series of the player's team
+fn step_team(
+    series: &mut [Series],
+    teams: &[Team],
+    player_teams: &PlayerTeamMap,
+    event: &WrappedEvent,
+    player: &str,
+    delta: i32,
+) {
+    let team = player_teams
+        .get_team_at(player, event.id)
+        .and_then(|t| teams.iter().position(|o| o.name == t.name));
+    if let Some(series) = team.and_then(|idx| series.get_mut(idx)) {
+        let value = series.last_value() + delta;
+        series.steps.push((event.time, value.max(0)));
+    }
+}
+
+fn team_scores(
+    events: &[WrappedEvent],
+    teams: &[Team],
+    player_teams: &PlayerTeamMap,
+) -> Vec<Series> {
+    let mut series = team_series(teams, |_| 0);
+    for event in events {
+        if let EventType::CaiCapture(capture) = &event.event {
+            step_team(
+                &mut series,
+                teams,
+                player_teams,
+                event,
+                capture.get_carrier(),
+                1,
+            );
+        }
+    }
+    series
+}
+
+fn players_alive(
+    events: &[WrappedEvent],
+    teams: &[Team],
+    player_teams: &PlayerTeamMap,
+) -> Vec<Series> {
+    let mut series = team_series(teams, |t| t.players.len() as i32);
+    for event in events {
+        let player = match &event.event {
+            EventType::TimvDeath(death) => death.get_player(),
+            EventType::HalloweenDeath(death) => death.get_player(),
+            // Players that respawn are still alive
+            EventType::HerdDeath(death) if !death.get_respawn() => death.get_player(),
+            _ => continue,
+        };
+        step_team(&mut series, teams, player_teams, event, player, -1);
+    }
+    series
+}
+
+fn survivors(events: &[WrappedEvent], teams: &[Team]) -> Vec<Series> {
+    let mut series = Series {
+        name: String::from("Survivors"),
+        color: teams.first().map(|t| t.color).unwrap_or("#000000"),
+        initial: teams.iter().map(|t| t.players.len() as i32).sum(),
+        steps: vec![],
+    };
+    for event in events {
+        if let EventType::BpDeath(death) = &event.event {
+            let value = series.last_value() - death.get_player().len() as i32;
+            series.steps.push((event.time, value.max(0)));
+        }
+    }
+    vec![series]
+}
+
+impl Series {
+    fn last_value(&self) -> i32 {
+        self.steps.last().map(|(_, v)| *v).unwrap_or(self.initial)
+    }
+}
+
+impl Chart {
+    pub fn to_svg(&self) -> String {
+        let end = self
+            .series
+            .iter()
+            .flat_map(|s| s.steps.iter().map(|(t, _)| *t))
+            .fold(self.end, i32::max)
+            .max(1);
+        let max = self
+            .series
+            .iter()
+            .flat_map(|s| s.steps.iter().map(|(_, v)| *v).chain(Some(s.initial)))
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        let plot_width = WIDTH - MARGIN_LEFT - MARGIN_RIGHT;
+        let plot_height = HEIGHT - MARGIN_TOP - MARGIN_BOTTOM;
+        let x = |time: i32| MARGIN_LEFT + time as f64 / end as f64 * plot_width;
+        let y = |value: i32| MARGIN_TOP + plot_height - value as f64 / max as f64 * plot_height;
+
+        let mut svg = String::new();
+        let _ = write!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {w} {h}" width="100%" role="img" font-family="sans-serif" font-size="12" fill="currentColor"><title>{title}</title>"#,
+            w = WIDTH,
+            h = HEIGHT,
+            title = self.title,
+        );
+
+        // Value axis
+        let value_step = ((max + 4) / 5).max(1);
+        for value in (0..=max).step_by(value_step as usize) {
+            let _ = write!(
+                svg,
+                r#"<line x1="{x1}" x2="{x2}" y1="{y:.1}" y2="{y:.1}" stroke="currentColor" stroke-opacity="0.15"/><text x="{tx}" y="{y:.1}" text-anchor="end" dominant-baseline="middle">{value}</text>"#,
+                x1 = MARGIN_LEFT,
+                x2 = WIDTH - MARGIN_RIGHT,
+                y = y(value),
+                tx = MARGIN_LEFT - 6.0,
+                value = value,
+            );
+        }
+
+        // Time axis
+        let time_step = TIME_STEPS
+            .iter()
+            .copied()
+            .find(|step| end / (step * 1000) < MAX_TIME_TICKS)
+            .unwrap_or(TIME_STEPS[TIME_STEPS.len() - 1])
+            * 1000;
+        for time in (0..=end).step_by(time_step as usize) {
+            let _ = write!(
+                svg,
+                r#"<text x="{x:.1}" y="{y}" text-anchor="middle">{label}</text>"#,
+                x = x(time),
+                y = MARGIN_TOP + plot_height + 16.0,
+                label = super::format_duration(time),
+            );
+        }
+
+        for series in &self.series {
+            let mut path = format!("M{:.1} {:.1}", x(0), y(series.initial));
+            for (time, value) in &series.steps {
+                let _ = write!(path, "H{:.1}V{:.1}", x(*time), y(*value));
+            }
+            let _ = write!(path, "H{:.1}", x(end));
+            let _ = write!(
+                svg,
+                r#"<path d="{path}" fill="none" stroke="{color}" stroke-width="2.5" stroke-linejoin="round"><title>{name}</title></path>"#,
+                path = path,
+                color = series.color,
+                name = xml_escape(&series.name),
+            );
+        }
+
+        // Legend
+        let mut legend_x = MARGIN_LEFT;
+        let legend_y = HEIGHT - 12.0;
+        for series in &self.series {
+            let _ = write!(
+                svg,
+                r#"<rect x="{x:.1}" y="{y:.1}" width="10" height="10" rx="2" fill="{color}"/><text x="{tx:.1}" y="{ty:.1}" dominant-baseline="middle">{name}</text>"#,
+                x = legend_x,
+                y = legend_y - 5.0,
+                color = series.color,
+                tx = legend_x + 14.0,
+                ty = legend_y,
+                name = xml_escape(&series.name),
+            );
+            legend_x += 28.0 + 7.0 * series.name.chars().count() as f64;
+        }
+
+        svg.push_str("</svg>");
+        svg
+    }
+}
+
+pub(super) fn xml_escape(input: &str) -> String {
+    input
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,225 @@
+use super::{
+    analyze_log, format_duration, get_log, get_teams, get_winners, map_entries, map_names,
+    parse_game_path, recap::join_names, AnalyzedLog, PlayerTeamMap, Summary, Team,
+};
+use crate::{
+    error::{Error, Result},
+    modes::GameMode,
+    web::SITE_URL,
+    AppState,
+};
+use actix_web::{http::Uri, web, HttpResponse};
+use serde::{Deserialize, Serialize};
+
+const CARD_WIDTH: u32 = 1200;
+const CARD_HEIGHT: u32 = 630;
+
+#[derive(Deserialize)]
+pub struct OEmbedQuery {
+    url: String,
+    format: Option<String>,
+}
+
+/// oEmbed "photo" response pointing at the game's summary card
+#[derive(Serialize)]
+struct OEmbed {
+    version: &'static str,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    title: String,
+    /// Discord shows the author name above the title, so it carries the description
+    author_name: String,
+    author_url: String,
+    provider_name: &'static str,
+    provider_url: &'static str,
+    url: String,
+    width: u32,
+    height: u32,
+    cache_age: u32,
+}
+
+pub async fn oembed(
+    state: web::Data<AppState>,
+    query: web::Query<OEmbedQuery>,
+) -> Result<HttpResponse> {
+    if query.format.as_deref().unwrap_or("json") != "json" {
+        return Ok(HttpResponse::NotImplemented().finish());
+    }
+    let (mode, path_id) = parse_game_url(&query.url).ok_or(Error::NotFound)?;
+    let (mode, id) = parse_game_path(mode, &path_id)?;
+    let (log, _) = get_log(state.clone(), mode, id, false).await?;
+
+    let teams = get_teams(&log);
+    let AnalyzedLog {
+        winner,
+        extension_ptr,
+        player_teams,
+        summary,
+        duration,
+        ..
+    } = analyze_log(mode, &log, &teams);
+
+    let game_url = format!("{}/game/{}/{}", *SITE_URL, mode.get_database_id(), path_id);
+    Ok(HttpResponse::Ok().json(OEmbed {
+        version: "1.0",
+        kind: "photo",
+        title: format!("{}: Game {}", mode.get_full_name(), path_id),
+        author_name: describe(
+            &map_names(&map_entries(
+                &state.maps,
+                mode,
+                &log,
+                &*extension_ptr,
+                &summary,
+            )),
+            duration,
+            &summary,
+            &teams,
+            winner.as_ref(),
+            &player_teams,
+        ),
+        url: format!("{}/card.png", game_url),
+        author_url: game_url,
+        provider_name: "KIG Network",
+        provider_url: &SITE_URL,
+        width: CARD_WIDTH,
+        height: CARD_HEIGHT,
+        cache_age: 3600,
+    }))
+}
+
+/// Address of the oEmbed response for a game, as advertised on its page
+pub(super) fn oembed_url(mode: GameMode, path_id: &str) -> String {
+    let game_url = format!("{}/game/{}/{}", *SITE_URL, mode.get_database_id(), path_id);
+    let encoded: String = game_url
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect();
+    format!("{}/oembed?url={}&format=json", *SITE_URL, encoded)
+}
+
+/// Extracts the mode and ID from one of our game URLs, e.g. `https://playkig.com/game/cai/abc`
+fn parse_game_url(url: &str) -> Option<(String, String)> {
+    let url: Uri = url.parse().ok()?;
+    let site: Uri = SITE_URL.parse().ok()?;
+    if !matches!(url.scheme_str(), Some("http") | Some("https"))
+        || url.host()? != site.host()?
+        || url.port_u16() != site.port_u16()
+    {
+        return None;
+    }
+    let path = url
+        .path()
+        .strip_prefix(site.path().trim_end_matches('/'))?
+        .strip_prefix("/game/")?;
+    let mut parts = path.trim_end_matches('/').split('/');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(mode), Some(id), None) if !mode.is_empty() && !id.is_empty() => {
+            Some((mode.to_string(), id.to_string()))
+        }
+        _ => None,
+    }
+}
+
+/// One-line description of the outcome of a game, e.g. "Red won 3–1 on Tumbleweed in 12:04"
+pub(super) fn describe(
+    map: &str,
+    duration: i32,
+    summary: &Summary,
+    teams: &[Team],
+    winner: Option<&Team>,
+    player_teams: &PlayerTeamMap,
+) -> String {
+    let on_map = if map.is_empty() {
+        String::new()
+    } else {
+        format!(" on {}", map)
+    };
+    let winners = join_names(
+        get_winners(summary, winner, player_teams)
+            .into_iter()
+            .map(|(name, _)| name),
+    );
+    let duration = format_duration(duration);
+
+    if winners.is_empty() {
+        return match summary {
+            Summary::Grav(splits) => format!(
+                "Nobody finished all {} stages in {}",
+                splits.stages.len(),
+                duration
+            ),
+            _ => format!("Ended without a winner{} after {}", on_map, duration),
+        };
+    }
+
+    match summary {
+        Summary::Grav(splits) => {
+            let finished = splits.rows.iter().filter(|r| r.finish.is_some()).count();
+            let time = splits
+                .rows
+                .iter()
+                .filter_map(|r| r.finish)
+                .find(|f| f.place == 1)
+                .and_then(|f| super::filters::grav_format_time(&f.time).ok())
+                .unwrap_or(duration);
+            format!(
+                "{} finished all {} stages first in {}; {} of {} players finished",
+                winners,
+                splits.stages.len(),
+                time,
+                finished,
+                splits.rows.len()
+            )
+        }
+        Summary::Bp(bp) => format!(
+            "{} won after {} rounds{} in {}",
+            winners,
+            bp.rounds.len(),
+            on_map,
+            duration
+        ),
+        Summary::Bed(bed) => {
+            let beds = bed.teams.iter().filter(|t| t.bed.is_some()).count();
+            format!(
+                "{} won{} in {} after {} of {} beds were destroyed",
+                winners,
+                on_map,
+                duration,
+                beds,
+                bed.teams.len()
+            )
+        }
+        Summary::Timv(timv) => {
+            let dead = timv
+                .players
+                .iter()
+                .filter(|p| p.death_cause.is_some())
+                .count();
+            format!(
+                "{} won{} in {}; {} of {} players died",
+                winners,
+                on_map,
+                duration,
+                dead,
+                timv.players.len()
+            )
+        }
+        _ => match (winner, teams) {
+            // Two scoring teams, e.g. Cowboys and Indians captures
+            (Some(winner), [a, b]) if a.score != 0 || b.score != 0 => {
+                let loser = if a.name == winner.name { b } else { a };
+                format!(
+                    "{} won {}\u{2013}{}{} in {}",
+                    winners, winner.score, loser.score, on_map, duration
+                )
+            }
+            _ => format!("{} won{} in {}", winners, on_map, duration),
+        },
+    }
+}
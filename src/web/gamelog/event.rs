@@ -45,3 +45,34 @@ pub enum EventType {
     // Halloween events
     HalloweenDeath(halloween::DeathEvent),
 }
+
+/// A player's death, regardless of the mode it happened in
+pub struct Kill<'a> {
+    pub victim: &'a str,
+    pub killer: Option<&'a str>,
+}
+
+impl EventType {
+    pub fn kill(&self) -> Option<Kill<'_>> {
+        let (victim, killer) = match self {
+            EventType::CaiDeath(death) => (
+                death.get_player(),
+                death.has_killer().then(|| death.get_killer()),
+            ),
+            EventType::TimvDeath(death) => (
+                death.get_player(),
+                death.has_killer().then(|| death.get_killer()),
+            ),
+            EventType::HerdDeath(death) => (
+                death.get_player(),
+                death.has_killer().then(|| death.get_killer()),
+            ),
+            EventType::HalloweenDeath(death) => (
+                death.get_player(),
+                death.has_killer().then(|| death.get_killer()),
+            ),
+            _ => return None,
+        };
+        Some(Kill { victim, killer })
+    }
+}
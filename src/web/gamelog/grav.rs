@@ -2,14 +2,47 @@ use std::borrow::Cow;
 
 use crate::protos::gamelog::GameLog;
 
-use super::{event::EventType, GameLogExtension};
+use super::{event::EventType, recap, GameLogExtension, WrappedEvent};
 use crate::protos::grav::exts::log_ext;
+use std::collections::HashMap;
 
 #[derive(Clone)]
 pub struct GravExtension {
     stages: Vec<String>,
 }
 
+/// Per-player split times, one column per stage
+pub struct SplitTable {
+    pub stages: Vec<String>,
+    pub rows: Vec<SplitRow>,
+}
+
+pub struct SplitRow {
+    pub player: String,
+    pub splits: Vec<StageSplit>,
+    pub finish: Option<Finish>,
+}
+
+#[derive(Clone, Copy)]
+pub struct Finish {
+    pub place: u32,
+    pub time: u64,
+}
+
+#[derive(Default, Clone)]
+pub struct StageSplit {
+    pub completion: Option<StageCompletion>,
+    pub hardcore_fails: u32,
+    pub eliminated: bool,
+}
+
+#[derive(Clone, Copy)]
+pub struct StageCompletion {
+    pub time: u64,
+    pub cumulative: u64,
+    pub skipped: bool,
+}
+
 impl GravExtension {
     pub fn new(log: &GameLog) -> GravExtension {
         GravExtension {
@@ -19,6 +52,130 @@ impl GravExtension {
                 .unwrap_or_else(|| Vec::new()),
         }
     }
+
+    /// Groups the stage completions and hardcore fails by player.
+    /// Stage completion times are per stage, the cumulative time is their running sum.
+    pub fn splits(&self, events: &[WrappedEvent]) -> SplitTable {
+        let stages = self.stages.len();
+        let mut rows: Vec<SplitRow> = vec![];
+        let mut indices: HashMap<&str, usize> = HashMap::new();
+        for event in events {
+            let player = match &event.event {
+                EventType::GravStageCompletion(e) => e.get_player(),
+                EventType::GravHardcoreFail(e) => e.get_player(),
+                EventType::GravGameFinish(e) => e.get_player(),
+                _ => continue,
+            };
+            let idx = *indices.entry(player).or_insert_with(|| {
+                rows.push(SplitRow {
+                    player: player.to_string(),
+                    splits: vec![StageSplit::default(); stages],
+                    finish: None,
+                });
+                rows.len() - 1
+            });
+            let row = &mut rows[idx];
+            match &event.event {
+                EventType::GravStageCompletion(e) => {
+                    if let Some(split) = row.splits.get_mut(e.get_stage_index() as usize) {
+                        split.completion = Some(StageCompletion {
+                            time: e.get_time(),
+                            cumulative: 0,
+                            skipped: e.get_skipped(),
+                        });
+                    }
+                }
+                EventType::GravHardcoreFail(e) => {
+                    if let Some(split) = row.splits.get_mut(e.get_stage() as usize) {
+                        split.hardcore_fails += 1;
+                        split.eliminated |= e.get_field_final();
+                    }
+                }
+                EventType::GravGameFinish(e) => {
+                    row.finish = Some(Finish {
+                        place: e.get_place(),
+                        time: e.get_time(),
+                    })
+                }
+                _ => {}
+            }
+        }
+        for row in &mut rows {
+            let mut total = 0;
+            for completion in row.splits.iter_mut().filter_map(|s| s.completion.as_mut()) {
+                total += completion.time;
+                completion.cumulative = total;
+            }
+        }
+        rows.sort_by_key(|r| {
+            let completed = r.splits.iter().filter(|s| s.completion.is_some()).count();
+            let total = r
+                .splits
+                .iter()
+                .filter_map(|s| s.completion)
+                .map(|c| c.time)
+                .sum::<u64>();
+            (
+                r.finish.map(|f| f.place).unwrap_or(u32::MAX),
+                std::cmp::Reverse(completed),
+                total,
+            )
+        });
+        SplitTable {
+            stages: self.stages.clone(),
+            rows,
+        }
+    }
+
+    /// Narrates who was fastest on the most stages, hardcore fails and eliminations
+    pub fn recap(&self, splits: &SplitTable) -> Vec<String> {
+        let mut lines = vec![];
+        let mut fastest: Vec<(&str, u32)> = vec![];
+        for stage in 0..splits.stages.len() {
+            let best = splits
+                .rows
+                .iter()
+                .filter_map(|row| {
+                    let completion = row.splits.get(stage)?.completion?;
+                    (!completion.skipped).then_some((row.player.as_str(), completion.time))
+                })
+                .min_by_key(|(_, time)| *time);
+            if let Some((player, _)) = best {
+                match fastest.iter_mut().find(|(name, _)| *name == player) {
+                    Some((_, count)) => *count += 1,
+                    None => fastest.push((player, 1)),
+                }
+            }
+        }
+        if let Some((player, stages)) = fastest.iter().max_by_key(|(_, count)| *count) {
+            lines.push(format!(
+                "{} was fastest on {} of {} stages",
+                player,
+                stages,
+                splits.stages.len()
+            ));
+        }
+        lines.extend(recap::top_clause(
+            splits.rows.iter().map(|row| {
+                (
+                    row.player.as_str(),
+                    row.splits.iter().map(|s| s.hardcore_fails).sum(),
+                )
+            }),
+            |n| format!("failed hardcore {}", recap::times(n)),
+        ));
+        let eliminated = splits
+            .rows
+            .iter()
+            .filter(|row| row.splits.iter().any(|s| s.eliminated))
+            .count();
+        match eliminated {
+            0 => {}
+            1 => lines.push(String::from("1 player was eliminated")),
+            n => lines.push(format!("{} players were eliminated", n)),
+        }
+        lines
+    }
 }
 
 impl GameLogExtension for GravExtension {
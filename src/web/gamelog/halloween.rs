@@ -1,8 +1,23 @@
-use super::{event::EventType, GameLogExtension};
+use super::{event::EventType, recap, GameLogExtension, WrappedEvent};
 
 #[derive(Clone, Copy)]
 pub struct HalloweenExtension {}
 
+impl HalloweenExtension {
+    /// Narrates the top killer and the last kill
+    pub fn recap(&self, events: &[WrappedEvent]) -> Vec<String> {
+        let mut lines: Vec<String> = recap::top_clause(recap::kill_counts(events), |n| {
+            format!("got {}", recap::plural(n, "kill"))
+        })
+        .into_iter()
+        .collect();
+        if let Some(last) = events.iter().rev().find(|e| e.event.kill().is_some()) {
+            lines.push(recap::decided_at(last.time));
+        }
+        lines
+    }
+}
+
 impl GameLogExtension for HalloweenExtension {
     fn get_box_color(&self, event: &super::EventType) -> &'static str {
         match event {
@@ -0,0 +1,495 @@
+use super::{
+    analyze_log, chat_channel, embed, encode_game_id, get_teams, get_winners, grav::SplitTable,
+    map_entries, map_names, parse_events, AnalyzedLog, EventType, PlayerTeamMap, Summary,
+};
+use crate::{
+    db::{ChatLine, DbHandle, GameLogIndex, GameLogMeta},
+    error::Result,
+    maps::MapManifest,
+    modes::GameMode,
+    privacy::public_log,
+    protos::gamelog::GameLog,
+    web::SITE_URL,
+    webhooks::{GameSummary, SummaryPlayer},
+    AppState,
+};
+use actix_web::web;
+use cached::{proc_macro::cached, TimedCache};
+use std::{collections::HashMap, future::Future, sync::Arc, time::Duration};
+use strum::IntoEnumIterator;
+
+/// Time between checks for new logs to index for searches
+const INDEX_INTERVAL: Duration = Duration::from_secs(300);
+/// Logs read from the database at once while indexing
+const INDEX_BATCH: i64 = 100;
+
+pub use crate::db::{IndexedGame, IndexedKill, IndexedPlayer, IndexedStage};
+
+impl IndexedGame {
+    pub fn player(&self, uuid: &str) -> Option<&IndexedPlayer> {
+        self.players.iter().find(|p| p.uuid == uuid)
+    }
+
+    /// The metadata stored alongside the log, for searches
+    fn stored(&self) -> GameLogIndex {
+        let keys = |p: &IndexedPlayer| [p.uuid.to_lowercase(), p.name.to_lowercase()];
+        let mut winner_keys: Vec<String> = self
+            .players
+            .iter()
+            .filter(|p| p.won)
+            .flat_map(keys)
+            .chain(self.winners.iter().map(|w| w.to_lowercase()))
+            .collect();
+        winner_keys.sort();
+        winner_keys.dedup();
+        let mut maps: Vec<String> = if self.stages.is_empty() {
+            vec![self.map.clone()]
+        } else {
+            self.stages.iter().map(|s| s.name.clone()).collect()
+        };
+        maps.retain(|m| !m.is_empty());
+        GameLogIndex {
+            start: self.start,
+            end: self.start + self.duration as i64,
+            maps,
+            players: self.players.iter().map(|p| p.name.clone()).collect(),
+            winners: self.winners.clone(),
+            player_keys: self.players.iter().flat_map(keys).collect(),
+            winner_keys,
+        }
+    }
+
+    /// Whether players were split in teams, rather than all playing for themselves
+    pub fn has_teams(&self) -> bool {
+        self.players
+            .first()
+            .is_some_and(|first| self.players.iter().any(|p| p.team != first.team))
+    }
+}
+
+/// Every public game of a mode, newest first, as stored by the indexer. Only for statistics over
+/// all games: lists and lookups query the database.
+#[cached(
+    ty = "TimedCache<GameMode, Arc<Vec<IndexedGame>>>",
+    create = "{ TimedCache::with_lifespan(Duration::from_secs(900)) }",
+    convert = "{ mode }",
+    result,
+    sync_writes = "default"
+)]
+pub async fn mode_games(
+    state: web::Data<AppState>,
+    mode: GameMode,
+) -> Result<Arc<Vec<IndexedGame>>> {
+    Ok(Arc::new(state.db.public_games(mode, &[], None).await?))
+}
+
+/// Analyses a log for the site pages
+fn index_log(maps: &MapManifest, mode: GameMode, id: &[u8], log: &GameLog) -> IndexedGame {
+    let teams = get_teams(log);
+    let AnalyzedLog {
+        winner,
+        extension_ptr,
+        events,
+        player_teams,
+        summary,
+        duration,
+        ..
+    } = analyze_log(mode, log, &teams);
+    let stages = match &summary {
+        Summary::Grav(splits) => stages(splits),
+        _ => vec![],
+    };
+    let winners: Vec<&str> = get_winners(&summary, winner.as_ref(), &player_teams)
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+
+    let uuids: HashMap<&str, String> = teams
+        .iter()
+        .flat_map(|t| t.players.iter())
+        .map(|p| (p.name, p.uuid.to_string()))
+        .collect();
+    IndexedGame {
+        mode,
+        id: encode_game_id(id),
+        start: log.get_game_start(),
+        duration,
+        map: extension_ptr.get_map(log).into_owned(),
+        description: embed::describe(
+            &map_names(&map_entries(maps, mode, log, &*extension_ptr, &summary)),
+            duration,
+            &summary,
+            &teams,
+            winner.as_ref(),
+            &player_teams,
+        ),
+        winners: winners.iter().map(|w| w.to_string()).collect(),
+        players: teams
+            .iter()
+            .flat_map(|t| t.players.iter().map(move |p| (t, p)))
+            .map(|(team, p)| IndexedPlayer {
+                uuid: p.uuid.to_string(),
+                name: p.name.to_string(),
+                nicked: p.is_nicked(),
+                team: team.name.to_string(),
+                won: winners.contains(&p.name) || winners.contains(&team.name),
+            })
+            .collect(),
+        kills: events
+            .iter()
+            .filter_map(|e| e.event.kill())
+            .filter_map(|kill| {
+                Some(IndexedKill {
+                    killer: uuids.get(kill.killer?)?.clone(),
+                    victim: uuids.get(kill.victim)?.clone(),
+                })
+            })
+            .collect(),
+        stages,
+        server: None,
+    }
+}
+
+/// Stores the search metadata and chat messages of every log that does not have them
+/// yet, then keeps checking for new logs.
+pub async fn index_logs(state: AppState) {
+    for mode in GameMode::iter() {
+        if let Err(e) = state.db.create_search_indexes(mode.get_database_id()).await {
+            eprintln!(
+                "Could not create {} search indexes: {}",
+                mode.get_database_id(),
+                e
+            );
+        }
+    }
+    if let Err(e) = state.db.create_chat_indexes().await {
+        eprintln!("Could not create chat search indexes: {}", e);
+    }
+    let new_since = DbHandle::now() - INDEX_INTERVAL.as_millis() as i64;
+    loop {
+        for mode in GameMode::iter() {
+            if let Err(e) = index_mode_logs(&state, mode, new_since).await {
+                eprintln!("Could not index {} logs: {}", mode.get_database_id(), e);
+            }
+        }
+        actix_web::rt::time::delay_for(INDEX_INTERVAL).await;
+    }
+}
+
+/// Indexes the logs of a mode that need it. Webhooks are notified of the games that ended
+/// after `new_since`, in milliseconds, and were never indexed.
+async fn index_mode_logs(state: &AppState, mode: GameMode, new_since: i64) -> Result<()> {
+    loop {
+        let logs = state
+            .db
+            .unindexed_game_logs(mode.get_database_id(), INDEX_BATCH)
+            .await?;
+        let done = (logs.len() as i64) < INDEX_BATCH;
+        index_batch(
+            mode,
+            logs,
+            |id, log, meta| async move {
+                index_mode_log(state, mode, id, &log, &meta, new_since).await
+            },
+            |id, error| async move {
+                state
+                    .db
+                    .set_game_log_index_error(mode.get_database_id(), id, &error)
+                    .await
+            },
+        )
+        .await?;
+        if done {
+            return Ok(());
+        }
+    }
+}
+
+/// Indexes a batch of logs one by one. A log that cannot be indexed is reported and marked as
+/// failed, so that it neither holds back the rest of the batch nor comes back in the next one.
+/// Only failing to mark it stops the batch, as the database is then likely unreachable.
+async fn index_batch<I, M>(
+    mode: GameMode,
+    logs: Vec<(Vec<u8>, GameLog, GameLogMeta)>,
+    mut index: impl FnMut(Vec<u8>, GameLog, GameLogMeta) -> I,
+    mut mark_failed: impl FnMut(Vec<u8>, String) -> M,
+) -> Result<()>
+where
+    I: Future<Output = Result<()>>,
+    M: Future<Output = Result<()>>,
+{
+    for (id, log, meta) in logs {
+        if let Err(e) = index(id.clone(), log, meta).await {
+            eprintln!(
+                "Could not index {} log {}: {}",
+                mode.get_database_id(),
+                encode_game_id(&id),
+                e
+            );
+            mark_failed(id, e.to_string()).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn index_mode_log(
+    state: &AppState,
+    mode: GameMode,
+    id: Vec<u8>,
+    log: &GameLog,
+    meta: &GameLogMeta,
+    new_since: i64,
+) -> Result<()> {
+    let game = index_log(&state.maps, mode, &id, log);
+    // Logs indexed for the first time are new games, unless they ended before the indexer
+    // started, e.g. after restoring a backup
+    let finished = !meta.indexed
+        && meta.hidden.is_none()
+        && game.start + game.duration as i64 > new_since
+        && state.webhooks.for_mode(mode).next().is_some();
+    // Site pages are public, so nicked players are only shown by their nick
+    let page = index_log(&state.maps, mode, &id, &public_log(mode, log)?);
+    let mut index = game.stored();
+    // Staff can look up nicked players by their nick too
+    index.player_keys.extend(
+        page.players
+            .iter()
+            .filter(|p| p.nicked)
+            .map(|p| p.name.to_lowercase()),
+    );
+    state
+        .db
+        .set_chat_index(
+            mode.get_database_id(),
+            id.clone(),
+            game.start,
+            &chat_lines(mode, log),
+        )
+        .await?;
+    state
+        .db
+        .set_game_log_index(mode.get_database_id(), id, &index, &page)
+        .await?;
+    if finished {
+        state
+            .webhooks
+            .game_finished(state.db.clone(), mode, summarize(mode, page));
+    }
+    Ok(())
+}
+
+/// Summary of a game for webhooks, with the names shown on public pages
+pub fn game_summary(
+    maps: &MapManifest,
+    mode: GameMode,
+    id: &[u8],
+    log: &GameLog,
+) -> Result<GameSummary> {
+    Ok(summarize(
+        mode,
+        index_log(maps, mode, id, &public_log(mode, log)?),
+    ))
+}
+
+fn summarize(mode: GameMode, game: IndexedGame) -> GameSummary {
+    GameSummary {
+        mode: mode.get_database_id(),
+        url: format!("{}/game/{}/{}", *SITE_URL, mode.get_database_id(), game.id),
+        winner: (!game.winners.is_empty()).then(|| game.winners.join(", ")),
+        map: game.stored().maps.join(", "),
+        start: game.start,
+        duration: game.duration,
+        players: game
+            .players
+            .into_iter()
+            .map(|p| SummaryPlayer {
+                name: p.name,
+                uuid: p.uuid,
+                team: p.team,
+                won: p.won,
+            })
+            .collect(),
+        id: game.id,
+    }
+}
+
+/// The chat messages of a log, for chat searches
+fn chat_lines(mode: GameMode, log: &GameLog) -> Vec<ChatLine> {
+    let teams = get_teams(log);
+    let extension = mode.to_gamelog_ext(log).boxed();
+    let events = parse_events(log, &*extension);
+    let player_teams = PlayerTeamMap::new(&teams, &events);
+    events
+        .iter()
+        .filter_map(|e| match &e.event {
+            EventType::Chat(chat) => Some(ChatLine {
+                event: e.id as u32,
+                time: e.time,
+                sender: chat.get_sender().into(),
+                message: chat.get_message().into(),
+                channel: chat_channel(e.id, chat, &teams, &player_teams)
+                    .name()
+                    .into(),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Looks up a player by name, as shown on public pages, as of the most recent game they played
+/// with it
+pub async fn find_player(db: &DbHandle, name: &str) -> Result<Option<IndexedPlayer>> {
+    let key = name.to_lowercase();
+    let mut latest: Option<IndexedGame> = None;
+    for mode in GameMode::iter() {
+        let game = db.public_games(mode, &[&key], Some(1)).await?.pop();
+        if let Some(game) = game.filter(|g| latest.as_ref().is_none_or(|l| g.start > l.start)) {
+            latest = Some(game);
+        }
+    }
+    Ok(latest.and_then(|game| {
+        game.players
+            .into_iter()
+            .find(|p| p.name.eq_ignore_ascii_case(name))
+    }))
+}
+
+fn stages(splits: &SplitTable) -> Vec<IndexedStage> {
+    splits
+        .stages
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let mut stage = IndexedStage {
+                name: name.clone(),
+                players: 0,
+                completions: vec![],
+                skips: 0,
+                hardcore_fails: 0,
+            };
+            for row in &splits.rows {
+                let split = match row.splits.get(i) {
+                    Some(split) => split,
+                    None => continue,
+                };
+                if split.completion.is_none() && split.hardcore_fails == 0 && !split.eliminated {
+                    continue;
+                }
+                stage.players += 1;
+                stage.hardcore_fails += split.hardcore_fails;
+                match split.completion {
+                    Some(completion) if completion.skipped => stage.skips += 1,
+                    Some(completion) => stage
+                        .completions
+                        .push((row.player.clone(), completion.time)),
+                    None => {}
+                }
+            }
+            stage
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{index_batch, IndexedGame, IndexedPlayer, IndexedStage};
+    use crate::{db::GameLogMeta, error::Error, modes::GameMode, protos::gamelog::GameLog};
+    use std::{cell::RefCell, rc::Rc};
+
+    #[test]
+    fn index_batch_marks_failed_logs_and_goes_on() {
+        let logs = (1..=3u8)
+            .map(|i| {
+                let meta = GameLogMeta {
+                    server: None,
+                    hidden: None,
+                    indexed: false,
+                };
+                (vec![i], GameLog::new(), meta)
+            })
+            .collect();
+        let indexed = Rc::new(RefCell::new(vec![]));
+        let failed = Rc::new(RefCell::new(vec![]));
+        let res = actix_web::rt::System::new("test").block_on({
+            let (indexed, failed) = (indexed.clone(), failed.clone());
+            async move {
+                index_batch(
+                    GameMode::CAI,
+                    logs,
+                    |id, _, _| {
+                        let indexed = indexed.clone();
+                        async move {
+                            if id == [2] {
+                                return Err(Error::Render);
+                            }
+                            indexed.borrow_mut().push(id);
+                            Ok(())
+                        }
+                    },
+                    |id, error| {
+                        let failed = failed.clone();
+                        async move {
+                            failed.borrow_mut().push((id, error));
+                            Ok(())
+                        }
+                    },
+                )
+                .await
+            }
+        });
+        assert!(res.is_ok());
+        assert_eq!(*indexed.borrow(), vec![vec![1], vec![3]]);
+        assert_eq!(*failed.borrow(), vec![(vec![2], "Render".to_string())]);
+    }
+
+    #[test]
+    fn stored_index_has_lowercase_keys_and_stage_maps() {
+        let player = |uuid: &str, name: &str, won| IndexedPlayer {
+            uuid: uuid.into(),
+            name: name.into(),
+            nicked: false,
+            team: "Players".into(),
+            won,
+        };
+        let stage = |name: &str| IndexedStage {
+            name: name.into(),
+            players: 2,
+            completions: vec![],
+            skips: 0,
+            hardcore_fails: 0,
+        };
+        let game = IndexedGame {
+            mode: GameMode::GRAV,
+            id: "abc".into(),
+            start: 1_600_000_000_000,
+            duration: 300_000,
+            map: "Tunnel".into(),
+            description: "Alice won in 05:00".into(),
+            winners: vec!["Alice".into()],
+            players: vec![
+                player("01010101-0101-0101-0101-0101010101AB", "Alice", true),
+                player("02020202-0202-0202-0202-020202020202", "Bob", false),
+            ],
+            kills: vec![],
+            stages: vec![stage("Tunnel"), stage(""), stage("Cliffs")],
+            server: None,
+        };
+        let index = game.stored();
+        assert_eq!(index.end, 1_600_000_300_000);
+        assert_eq!(index.maps, vec!["Tunnel", "Cliffs"]);
+        assert_eq!(index.players, vec!["Alice", "Bob"]);
+        assert_eq!(
+            index.player_keys,
+            vec![
+                "01010101-0101-0101-0101-0101010101ab",
+                "alice",
+                "02020202-0202-0202-0202-020202020202",
+                "bob",
+            ]
+        );
+        assert_eq!(
+            index.winner_keys,
+            vec!["01010101-0101-0101-0101-0101010101ab", "alice"]
+        );
+    }
+}
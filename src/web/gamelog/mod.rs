@@ -14,35 +14,48 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use crate::{
-    db::GameLogMeta,
-    error::Result,
+    auth::Role,
+    db::{GameLogMeta, HiddenLog, StaffUser},
+    error::{Error, Result},
+    maps::{Credit, MapEntry, MapManifest},
     modes::GameMode,
+    privacy,
     protos::gamelog::{self, ChatEvent_ChatType, GameLog, TimeEvent},
-    web::get_current_year,
+    redact::ChatRedactor,
+    web::{get_current_year, staff::Staff},
     AppState,
 };
 use actix_web::{
-    http::header::{ContentType, IntoHeaderValue},
+    http::header::{self, ContentType, IntoHeaderValue},
     web, HttpResponse,
 };
 use askama::Template;
-use cached::{proc_macro::cached, TimedCache};
+use cached::{proc_macro::cached, Cached, TimedCache};
+pub use card::card_png;
+pub use chart::chart_svg;
+pub use embed::oembed;
 use event::EventType::{self, *};
 use gamelog::{BukkitDamageCause, ChatEvent, GameEvent};
-use regex::Regex;
+pub use index::{game_summary, index_logs};
+use serde::Deserialize;
 use std::{borrow::Cow, str::FromStr};
-use std::{collections::HashMap, convert::TryInto, fmt, time::Duration};
+use std::{collections::HashMap, convert::TryInto, fmt, sync::Arc, time::Duration};
+use strum::IntoEnumIterator;
 
 mod bed;
 mod bp;
 mod cai;
+mod card;
+mod chart;
+mod embed;
 mod event;
 mod grav;
 mod halloween;
+pub(super) mod index;
+mod recap;
 mod timv;
 
 lazy_static::lazy_static! {
-    static ref MAP_ESCAPE_REGEX: Regex = Regex::new(r#"[^a-zA-Z0-9]"#).unwrap();
     static ref SPECTATORS: Team<'static> = Team {
       name: "Spec",
       players: vec![],
@@ -65,7 +78,32 @@ struct GamelogTemplate<'a> {
     mode: GameMode,
     functions: Functions,
     extension: WrappedExtension,
+    summary: Summary,
+    chart: Option<chart::Chart>,
+    maps: Vec<MapEntry<'a>>,
+    description: String,
+    recap: recap::Recap,
     server: Option<String>,
+    redactor: Option<Arc<ChatRedactor>>,
+    /// The staff member viewing the page, shown moderation controls
+    staff: Option<&'a StaffUser>,
+    hidden: Option<HiddenLog>,
+    /// Whether the visitor was sent back to the page after reporting an event
+    reported: bool,
+    current_year: String,
+}
+
+#[derive(Deserialize)]
+pub struct GamelogQuery {
+    reported: Option<String>,
+}
+
+/// Shown in place of games hidden from public view
+#[derive(Template)]
+#[template(path = "removed.html")]
+struct RemovedTemplate<'a> {
+    mode: GameMode,
+    game_id: &'a str,
     current_year: String,
 }
 
@@ -95,6 +133,22 @@ pub enum WrappedExtension {
     Halloween(halloween::HalloweenExtension),
 }
 
+/// Mode-specific overview of the game, rendered above the event list
+pub enum Summary {
+    None,
+    Grav(grav::SplitTable),
+    Cai(cai::CaiSummary),
+    Timv(timv::TimvSummary),
+    Bp(bp::BpSummary),
+    Bed(bed::BedSummary),
+}
+
+impl Summary {
+    fn is_none(&self) -> bool {
+        matches!(self, Summary::None)
+    }
+}
+
 impl WrappedExtension {
     fn boxed(self) -> Box<dyn GameLogExtension> {
         use self::WrappedExtension::*;
@@ -107,6 +161,25 @@ impl WrappedExtension {
             Halloween(ext) => Box::new(ext),
         }
     }
+
+    fn summary(
+        &self,
+        events: &[WrappedEvent],
+        teams: &[Team],
+        player_teams: &PlayerTeamMap,
+        winner: Option<&str>,
+    ) -> Summary {
+        match self {
+            WrappedExtension::Grav(ext) => Summary::Grav(ext.splits(events)),
+            WrappedExtension::Cai(ext) => Summary::Cai(ext.summary(events, player_teams)),
+            WrappedExtension::Timv(ext) => Summary::Timv(ext.summary(events, teams, player_teams)),
+            WrappedExtension::Bp(ext) => Summary::Bp(ext.summary(events, teams)),
+            WrappedExtension::Bed(ext) => {
+                Summary::Bed(ext.summary(events, teams, player_teams, winner))
+            }
+            _ => Summary::None,
+        }
+    }
 }
 
 impl GameMode {
@@ -161,109 +234,350 @@ enum ChatChannel<'a> {
 
 pub struct PlayerTeamMap<'a>(HashMap<&'a str, Vec<(usize, &'a Team<'a>)>>);
 
+/// Retrieves a log. Outside of staff views, nicked players are only shown by their nick
+/// and hidden games are [`Error::Removed`].
 #[cached(
-    ty = "TimedCache<(Vec<u8>, GameMode), (GameLog, GameLogMeta)>",
+    ty = "TimedCache<(Vec<u8>, GameMode, bool), (GameLog, GameLogMeta)>",
     create = "{ TimedCache::with_lifespan(Duration::from_secs(120)) }",
-    convert = "{ (id.clone(), mode) }",
+    convert = "{ (id.clone(), mode, staff) }",
     result
 )]
-async fn get_log(
+pub(super) async fn get_log(
     state: web::Data<AppState>,
     mode: GameMode,
     id: Vec<u8>,
+    staff: bool,
 ) -> Result<(GameLog, GameLogMeta)> {
-    state
+    let (log, meta) = state
         .db
         .game_log_by_id(mode.get_database_id(), id.clone())
         .await
-        .and_then(|opt| opt.ok_or(crate::error::Error::NotFound))
+        .and_then(|opt| opt.ok_or(Error::NotFound))?;
+    if staff {
+        Ok((log, meta))
+    } else if meta.hidden.is_some() {
+        Err(Error::Removed)
+    } else {
+        Ok((privacy::public_log(mode, &log)?, meta))
+    }
 }
 
-pub async fn gamelog_by_id(
-    state: web::Data<AppState>,
-    web::Path((mut mode, path_id)): web::Path<(String, String)>,
-) -> Result<HttpResponse> {
-    match base62::decode(&path_id) {
-        Ok(id) => {
-            mode.make_ascii_uppercase();
-            let mode = GameMode::from_str(&mode).map_err(|_| crate::error::Error::ModeNotFound)?;
-            let (log, meta) = get_log(state, mode, id.to_be_bytes()[2..].to_vec()).await?;
-            let teams: Vec<Team> = log
-                .get_teams()
+/// Drops the cached copies of a log, after staff hid, showed or deleted it
+pub(super) async fn forget_log(mode: GameMode, id: &[u8]) {
+    let mut logs = GET_LOG.lock().await;
+    for staff in [false, true] {
+        logs.cache_remove(&(id.to_vec(), mode, staff));
+    }
+    drop(logs);
+    card::forget_card(mode, id).await;
+    index::MODE_GAMES.lock().await.cache_remove(&mode);
+    super::halloween::forget_edition(mode).await;
+    super::home::forget_mode(mode).await;
+}
+
+/// The maps the game was played on, one per stage in Gravity
+fn map_entries<'a>(
+    manifest: &'a MapManifest,
+    mode: GameMode,
+    log: &GameLog,
+    extension: &dyn GameLogExtension,
+    summary: &Summary,
+) -> Vec<MapEntry<'a>> {
+    match summary {
+        Summary::Grav(splits) => splits
+            .stages
+            .iter()
+            .map(|stage| manifest.get(mode, stage))
+            .collect(),
+        _ => vec![manifest.get(mode, &extension.get_map(log))],
+    }
+}
+
+fn map_names(maps: &[MapEntry]) -> String {
+    maps.iter()
+        .map(|m| m.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Parses the mode and base62 game ID from a game URL
+pub fn parse_game_path(mode: String, path_id: &str) -> Result<(GameMode, Vec<u8>)> {
+    Ok((parse_mode(mode)?, parse_game_id(path_id)?))
+}
+
+/// Decodes a base62 game ID into its stored form
+pub(super) fn parse_game_id(path_id: &str) -> Result<Vec<u8>> {
+    let id = base62::decode(path_id).map_err(|_| Error::InvalidGameId)?;
+    Ok(id.to_be_bytes()[2..].to_vec())
+}
+
+/// Parses a mode from its database ID, as found in URLs
+pub(super) fn parse_mode(mut mode: String) -> Result<GameMode> {
+    mode.make_ascii_uppercase();
+    GameMode::from_str(&mode).map_err(|_| Error::ModeNotFound)
+}
+
+fn get_teams(log: &GameLog) -> Vec<Team<'_>> {
+    log.get_teams()
+        .iter()
+        .enumerate()
+        .map(|(i, t)| Team {
+            name: t.get_name(),
+            score: t.get_score(),
+            color: get_team_color(t, i),
+            players: t
+                .get_players()
                 .iter()
-                .enumerate()
-                .map(|(i, t)| Team {
-                    name: t.get_name(),
-                    score: t.get_score(),
-                    color: get_team_color(t, i),
-                    players: t
-                        .get_players()
-                        .iter()
-                        .map(|p| Player {
-                            name: p.get_name(),
-                            uuid: p.get_uuid().into(),
-                            nick: p.has_nick().then(|| p.get_nick()),
-                        })
-                        .collect(),
+                .map(|p| Player {
+                    name: p.get_name(),
+                    uuid: p.get_uuid().into(),
+                    nick: p.has_nick().then(|| p.get_nick()),
                 })
-                .collect();
-            let winner = log
-                .has_winner()
-                .then(|| log.get_winner())
-                .and_then(|winner| {
-                    teams
-                        .iter()
-                        .find(|t| t.name == winner)
-                        .cloned()
-                        .or_else(|| {
-                            Some(Team {
-                                name: winner,
-                                color: "",
-                                score: 0,
-                                players: vec![],
-                            })
-                        })
-                });
-
-            let extension = mode.to_gamelog_ext(&log);
-            let extension_ptr = extension.clone().boxed();
-
-            let events: Vec<WrappedEvent> = log
-                .get_events()
-                .iter()
-                .enumerate()
-                .map(|(i, e)| WrappedEvent::parse(i, e, &*extension_ptr))
-                .collect();
+                .collect(),
+        })
+        .collect()
+}
+
+/// The winning team, or a placeholder team if the winner is not one of the teams
+fn get_winner<'a>(log: &'a GameLog, teams: &[Team<'a>]) -> Option<Team<'a>> {
+    log.has_winner().then(|| log.get_winner()).map(|winner| {
+        teams
+            .iter()
+            .find(|t| t.name == winner)
+            .cloned()
+            .unwrap_or_else(|| Team {
+                name: winner,
+                color: "",
+                score: 0,
+                players: vec![],
+            })
+    })
+}
 
-            let player_teams = PlayerTeamMap::new(&teams, &events);
+fn parse_events(log: &GameLog, extension: &dyn GameLogExtension) -> Vec<WrappedEvent> {
+    log.get_events()
+        .iter()
+        .enumerate()
+        .map(|(i, e)| WrappedEvent::parse(i, e, extension))
+        .collect()
+}
 
-            let render = GamelogTemplate {
-                log: &log,
-                total_players: if log.get_start_players() == 0 {
-                    log.get_teams().iter().map(|t| t.get_players().len()).sum()
-                } else {
-                    log.get_start_players() as usize
-                },
-                game_id: &path_id,
-                teams: teams.clone(),
-                events,
-                player_teams,
-                winner,
+/// Everything the game page, card, oEmbed and site index derive from a log and its teams
+struct AnalyzedLog<'a> {
+    winner: Option<Team<'a>>,
+    extension: WrappedExtension,
+    extension_ptr: Box<dyn GameLogExtension>,
+    events: Vec<WrappedEvent>,
+    player_teams: PlayerTeamMap<'a>,
+    summary: Summary,
+    /// Game length in milliseconds
+    duration: i32,
+}
+
+fn analyze_log<'a>(mode: GameMode, log: &'a GameLog, teams: &'a [Team<'a>]) -> AnalyzedLog<'a> {
+    let winner = get_winner(log, teams);
+    let extension = mode.to_gamelog_ext(log);
+    let extension_ptr = extension.clone().boxed();
+    let events = parse_events(log, &*extension_ptr);
+    let player_teams = PlayerTeamMap::new(teams, &events);
+    let summary = extension.summary(
+        &events,
+        teams,
+        &player_teams,
+        winner.as_ref().map(|t| t.name),
+    );
+    AnalyzedLog {
+        winner,
+        extension,
+        extension_ptr,
+        events,
+        player_teams,
+        summary,
+        duration: (log.get_game_end() - log.get_game_start()) as i32,
+    }
+}
+
+/// Whether players can report the event with the given index
+pub(super) fn is_reportable_event(mode: GameMode, log: &GameLog, index: usize) -> bool {
+    let extension = mode.to_gamelog_ext(log).boxed();
+    log.get_events()
+        .get(index)
+        .is_some_and(|e| WrappedEvent::parse(index, e, &*extension).is_reportable())
+}
+
+/// Redirects a bare game ID to its page, looking up the mode it was played in
+pub async fn short_link(
+    state: web::Data<AppState>,
+    web::Path(path_id): web::Path<String>,
+) -> Result<HttpResponse> {
+    let id = parse_game_id(&path_id)?;
+    let db = &state.db;
+    let found = futures::future::try_join_all(GameMode::iter().map(|mode| {
+        let id = id.clone();
+        async move {
+            let found = db.has_game_log(mode.get_database_id(), id).await?;
+            Ok::<_, Error>(found.then_some(mode))
+        }
+    }))
+    .await?;
+    let mode = found.into_iter().flatten().next().ok_or(Error::NotFound)?;
+    Ok(HttpResponse::Found()
+        .header(
+            header::LOCATION,
+            format!("/game/{}/{}", mode.get_database_id(), path_id),
+        )
+        .finish())
+}
+
+pub async fn gamelog_by_id(
+    state: web::Data<AppState>,
+    staff: Option<Staff>,
+    web::Path((mode, path_id)): web::Path<(String, String)>,
+    query: web::Query<GamelogQuery>,
+) -> Result<HttpResponse> {
+    let (mode, id) = parse_game_path(mode, &path_id)?;
+    let (log, meta) = match get_log(state.clone(), mode, id, staff.is_some()).await {
+        Err(Error::Removed) => {
+            let render = RemovedTemplate {
                 mode,
-                functions: Functions {
-                    extension: extension_ptr,
-                },
-                extension,
-                server: meta.server,
+                game_id: &path_id,
                 current_year: get_current_year(),
             }
             .render()
             .unwrap();
-            Ok(HttpResponse::Ok()
+            return Ok(HttpResponse::Gone()
                 .content_type(IntoHeaderValue::try_into(ContentType::html()).unwrap())
-                .body(render))
+                .body(render));
         }
-        Err(_) => Ok(HttpResponse::BadRequest().body("Invalid game ID")),
+        res => res?,
+    };
+    let teams = get_teams(&log);
+    let AnalyzedLog {
+        winner,
+        extension,
+        extension_ptr,
+        events,
+        player_teams,
+        summary,
+        duration,
+    } = analyze_log(mode, &log, &teams);
+
+    let chart = extension.chart(&events, &teams, &player_teams, duration);
+    let maps = map_entries(&state.maps, mode, &log, &*extension_ptr, &summary);
+    let description = embed::describe(
+        &map_names(&maps),
+        duration,
+        &summary,
+        &teams,
+        winner.as_ref(),
+        &player_teams,
+    );
+
+    let recap = extension.recap(&summary, &events, &teams);
+
+    let render = GamelogTemplate {
+        log: &log,
+        total_players: if log.get_start_players() == 0 {
+            log.get_teams().iter().map(|t| t.get_players().len()).sum()
+        } else {
+            log.get_start_players() as usize
+        },
+        game_id: &path_id,
+        teams: teams.clone(),
+        events,
+        player_teams,
+        winner,
+        mode,
+        functions: Functions {
+            extension: extension_ptr,
+        },
+        extension,
+        summary,
+        chart,
+        maps,
+        description,
+        recap,
+        server: meta.server,
+        // Staff see chat messages as they were sent
+        redactor: staff.is_none().then(|| state.redaction.rules()),
+        staff: staff.as_ref().map(|s| &s.0),
+        hidden: meta.hidden,
+        reported: query.reported.is_some(),
+        current_year: get_current_year(),
+    }
+    .render()
+    .unwrap();
+    Ok(HttpResponse::Ok()
+        .content_type(IntoHeaderValue::try_into(ContentType::html()).unwrap())
+        .body(render))
+}
+
+impl GamelogTemplate<'_> {
+    fn can_hide(&self) -> bool {
+        self.staff.is_some_and(|s| s.role >= Role::Moderator)
+    }
+
+    fn can_delete(&self) -> bool {
+        self.staff.is_some_and(|s| s.role >= Role::Admin)
+    }
+
+    /// A chat message, redacted unless the page is viewed by staff
+    fn chat_message<'m>(&self, chat: &'m ChatEvent) -> Cow<'m, str> {
+        match &self.redactor {
+            Some(redactor) => redactor.redact(chat.get_message()),
+            None => Cow::Borrowed(chat.get_message()),
+        }
+    }
+
+    fn map_names(&self) -> String {
+        map_names(&self.maps)
+    }
+
+    fn oembed_url(&self) -> String {
+        embed::oembed_url(self.mode, self.game_id)
+    }
+
+    /// Screenshot of the first map of the game
+    fn map_image(&self) -> &str {
+        self.maps.first().map_or("", |m| m.image.as_str())
+    }
+
+    /// Screenshot credits of every map of the game, without duplicates
+    fn credits(&self) -> Vec<&Credit> {
+        let mut credits: Vec<&Credit> = vec![];
+        for credit in self.maps.iter().flat_map(|m| m.credits.iter()) {
+            if !credits.iter().any(|c| c.uuid == credit.uuid) {
+                credits.push(credit);
+            }
+        }
+        credits
+    }
+
+    /// Names and colors to show as the winners in the page header
+    fn winners(&self) -> Vec<(&str, &str)> {
+        get_winners(&self.summary, self.winner.as_ref(), &self.player_teams)
+    }
+}
+
+/// Names and colors of the winners: every winning player if the mode has them, otherwise the winning team
+fn get_winners<'a>(
+    summary: &'a Summary,
+    winner: Option<&'a Team>,
+    player_teams: &'a PlayerTeamMap,
+) -> Vec<(&'a str, &'a str)> {
+    match summary {
+        Summary::Bp(bp) if !bp.winners.is_empty() => bp
+            .winners
+            .iter()
+            .map(|name| {
+                let color = player_teams
+                    .get_team_at(name, usize::MAX)
+                    .map(|t| t.color)
+                    .unwrap_or("");
+                (name.as_str(), color)
+            })
+            .collect(),
+        _ => winner.iter().map(|t| (t.name, t.color)).collect(),
     }
 }
 
@@ -276,10 +590,6 @@ impl Functions {
             _ => self.extension.get_box_color(&event.event),
         }
     }
-
-    fn get_map<'slf, 'log: 'slf>(&'slf self, log: &'log GameLog) -> Cow<str> {
-        self.extension.get_map(log)
-    }
 }
 
 impl WrappedEvent {
@@ -330,28 +640,53 @@ impl WrappedEvent {
         event: &ChatEvent,
         log: &GamelogTemplate<'a>,
     ) -> ChatChannel<'a> {
-        if let EventType::Chat(chat_event) = &self.event {
-            match chat_event.get_field_type() {
-                ChatEvent_ChatType::LOBBY => ChatChannel::Static("Lobby"),
-                ChatEvent_ChatType::TEAM => if event.has_team() {
-                    log.teams.get(event.get_team() as usize)
-                } else {
-                    log.player_teams.get_team_at(event.get_sender(), *event_id)
-                }
-                .map(|t| ChatChannel::Team(t.name, t.color))
-                .unwrap_or_else(|| ChatChannel::Team(SPECTATORS.name, SPECTATORS.color)),
-                ChatEvent_ChatType::SHOUT => ChatChannel::Static("Shout"),
-                ChatEvent_ChatType::BROADCAST => ChatChannel::Static("Broadcast"),
-                ChatEvent_ChatType::GLOBAL => ChatChannel::None,
-            }
-        } else {
-            ChatChannel::None
+        match &self.event {
+            EventType::Chat(_) => chat_channel(*event_id, event, &log.teams, &log.player_teams),
+            _ => ChatChannel::None,
         }
     }
 
     fn is_chat(&self) -> bool {
         matches!(self.event, EventType::Chat(_))
     }
+
+    /// Players can report chat messages and kills
+    fn is_reportable(&self) -> bool {
+        self.is_chat() || self.event.kill().is_some()
+    }
+}
+
+/// The channel a chat message was sent in
+fn chat_channel<'a>(
+    event_id: usize,
+    event: &ChatEvent,
+    teams: &[Team<'a>],
+    player_teams: &PlayerTeamMap<'a>,
+) -> ChatChannel<'a> {
+    match event.get_field_type() {
+        ChatEvent_ChatType::LOBBY => ChatChannel::Static("Lobby"),
+        ChatEvent_ChatType::TEAM => if event.has_team() {
+            teams.get(event.get_team() as usize)
+        } else {
+            player_teams.get_team_at(event.get_sender(), event_id)
+        }
+        .map(|t| ChatChannel::Team(t.name, t.color))
+        .unwrap_or_else(|| ChatChannel::Team(SPECTATORS.name, SPECTATORS.color)),
+        ChatEvent_ChatType::SHOUT => ChatChannel::Static("Shout"),
+        ChatEvent_ChatType::BROADCAST => ChatChannel::Static("Broadcast"),
+        ChatEvent_ChatType::GLOBAL => ChatChannel::None,
+    }
+}
+
+impl ChatChannel<'_> {
+    /// The name shown in the channel badge, empty for the global chat
+    fn name(&self) -> &str {
+        match self {
+            ChatChannel::Static(name) => name,
+            ChatChannel::Team(name, _) => name,
+            ChatChannel::None => "",
+        }
+    }
 }
 
 impl BukkitDamageCause {
@@ -400,6 +735,31 @@ impl<'a> PlayerTeamMap<'a> {
     }
 }
 
+impl Player<'_> {
+    /// Whether only the nick of the player is known, as on public pages
+    fn is_nicked(&self) -> bool {
+        self.nick == Some(self.name)
+    }
+
+    /// The nick shown next to the real name of the player, on staff pages
+    fn shown_nick(&self) -> Option<&str> {
+        self.nick.filter(|nick| *nick != self.name)
+    }
+
+    fn avatar(&self) -> String {
+        avatar_url(&self.uuid.to_string(), self.name, self.is_nicked())
+    }
+}
+
+/// Avatar of a player. Nicks are resolved by name, as their UUID is not the one of an account.
+pub(super) fn avatar_url(uuid: &str, name: &str, nicked: bool) -> String {
+    if nicked {
+        format!("https://mc-heads.net/avatar/{}", name)
+    } else {
+        format!("https://crafatar.com/avatars/{}", uuid)
+    }
+}
+
 impl From<&[u8]> for UUID {
     fn from(bytes: &[u8]) -> Self {
         assert_eq!(bytes.len(), 16);
@@ -460,13 +820,20 @@ fn digits(val: u64, n: usize) -> String {
     format!("{:x}", (high | val & (high - 1u64)))
 }
 
+/// Encodes a stored game ID into the base62 form used in URLs
+pub fn encode_game_id(id: &[u8]) -> String {
+    let mut bytes = [0u8; 8];
+    bytes[8 - id.len().min(8)..].copy_from_slice(&id[id.len().saturating_sub(8)..]);
+    base62::encode(u64::from_be_bytes(bytes))
+}
+
 fn format_duration(millis: i32) -> String {
     let minutes = millis / (1000 * 60);
     let seconds = millis / 1000 % 60;
     format!("{:02}:{:02}", minutes, seconds)
 }
 
-mod filters {
+pub(super) mod filters {
     use crate::web::gamelog::PlayerTeamMap;
 
     pub use super::grav::filters::*;
@@ -482,15 +849,6 @@ mod filters {
         Ok(super::format_duration(*millis))
     }
 
-    pub fn map_file_name(map_name: &str) -> askama::Result<String> {
-        if map_name.is_empty() {
-            return Ok(String::from("default"));
-        }
-        let mut res: String = super::MAP_ESCAPE_REGEX.replace_all(map_name, "").into();
-        res.make_ascii_lowercase();
-        Ok(res)
-    }
-
     pub fn team_from_idx<'a>(idx: &'a i32, teams: &'a [Team<'a>]) -> askama::Result<&'a Team<'a>> {
         Ok(teams.get(*idx as usize).unwrap_or(&super::SPECTATORS))
     }
@@ -0,0 +1,110 @@
+use super::{format_duration, Summary, Team, WrappedEvent, WrappedExtension};
+use std::fmt;
+
+/// Short written account of a game, made of clauses like "alice got 7 kills"
+pub struct Recap(Vec<String>);
+
+impl Recap {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Display for Recap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.join("; "))
+    }
+}
+
+impl WrappedExtension {
+    /// Asks the mode's narrator for a recap, based on the mode summary where there is one
+    pub(super) fn recap(
+        &self,
+        summary: &Summary,
+        events: &[WrappedEvent],
+        teams: &[Team],
+    ) -> Recap {
+        Recap(match (self, summary) {
+            (WrappedExtension::Cai(ext), Summary::Cai(cai)) => ext.recap(cai, teams),
+            (WrappedExtension::Timv(ext), Summary::Timv(timv)) => ext.recap(timv, events),
+            (WrappedExtension::Bp(ext), Summary::Bp(bp)) => ext.recap(bp),
+            (WrappedExtension::Grav(ext), Summary::Grav(splits)) => ext.recap(splits),
+            (WrappedExtension::Bed(ext), Summary::Bed(bed)) => ext.recap(bed),
+            (WrappedExtension::Halloween(ext), _) => ext.recap(events),
+            _ => vec![],
+        })
+    }
+}
+
+/// Kills per killer, most first. Suicides are not counted.
+pub(super) fn kill_counts(events: &[WrappedEvent]) -> Vec<(&str, u32)> {
+    let mut kills: Vec<(&str, u32)> = vec![];
+    for killer in events
+        .iter()
+        .filter_map(|e| e.event.kill())
+        .filter_map(|k| k.killer.filter(|killer| *killer != k.victim))
+    {
+        match kills.iter_mut().find(|(name, _)| *name == killer) {
+            Some((_, count)) => *count += 1,
+            None => kills.push((killer, 1)),
+        }
+    }
+    kills.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    kills
+}
+
+/// Describes the players with the highest non-zero count, e.g. "alice and bob got 3 kills each"
+pub(super) fn top_clause<'a>(
+    counts: impl IntoIterator<Item = (&'a str, u32)>,
+    describe: impl Fn(u32) -> String,
+) -> Option<String> {
+    let counts: Vec<(&str, u32)> = counts.into_iter().filter(|(_, c)| *c > 0).collect();
+    let max = counts.iter().map(|(_, c)| *c).max()?;
+    let names: Vec<&str> = counts
+        .iter()
+        .filter(|(_, c)| *c == max)
+        .map(|(name, _)| *name)
+        .collect();
+    Some(format!(
+        "{} {}{}",
+        join_names(names.iter().copied()),
+        describe(max),
+        if names.len() > 1 { " each" } else { "" }
+    ))
+}
+
+pub(super) fn decided_at(time: i32) -> String {
+    format!("game decided at {}", format_duration(time))
+}
+
+/// "once", "twice", "3 times"
+pub(super) fn times(count: u32) -> String {
+    match count {
+        1 => String::from("once"),
+        2 => String::from("twice"),
+        n => format!("{} times", n),
+    }
+}
+
+/// "Red's", "Cowboys'"
+pub(super) fn possessive(name: &str) -> String {
+    if name.ends_with('s') {
+        format!("{}'", name)
+    } else {
+        format!("{}'s", name)
+    }
+}
+
+pub(super) fn plural(count: u32, word: &str) -> String {
+    format!("{} {}{}", count, word, if count == 1 { "" } else { "s" })
+}
+
+/// Joins names as "alice", "alice and bob" or "alice, bob and carol"
+pub(super) fn join_names<'a>(names: impl Iterator<Item = &'a str>) -> String {
+    let names: Vec<&str> = names.collect();
+    match names.split_last() {
+        None => String::new(),
+        Some((last, [])) => last.to_string(),
+        Some((last, rest)) => format!("{} and {}", rest.join(", "), last),
+    }
+}
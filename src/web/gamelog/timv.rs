@@ -1,10 +1,209 @@
 use crate::protos::timv::{DeathEvent, DeathEvent_DeathCause};
 
-use super::{event::EventType, GameLogExtension};
+use super::{
+    event::EventType, recap, GameLogExtension, PlayerTeamMap, Team, WrappedEvent, SPECTATORS,
+};
 
 #[derive(Clone, Copy)]
 pub struct TimvExtension {}
 
+/// Roles, tests and body identifications of a round
+pub struct TimvSummary {
+    pub players: Vec<RoleSummary>,
+    pub identifications: Vec<Identification>,
+    pub psychic_reports: Vec<PsychicReport>,
+    pub purchases: Vec<Purchase>,
+}
+
+pub struct RoleSummary {
+    pub name: String,
+    pub role: String,
+    pub color: &'static str,
+    pub kills: Vec<Kill>,
+    pub tests: u32,
+    pub spoofed: bool,
+    pub killed_by: Option<String>,
+    pub death_cause: Option<&'static str>,
+    pub identified_by: Option<String>,
+}
+
+pub struct Kill {
+    pub victim: String,
+    pub color: &'static str,
+    pub assassin_target: bool,
+}
+
+pub struct Identification {
+    pub time: i32,
+    pub body: String,
+    pub body_color: &'static str,
+    pub identifier: String,
+    pub identifier_color: &'static str,
+    /// Whether the body was inspected by a detective, rather than just found
+    pub detective: bool,
+}
+
+pub struct PsychicReport {
+    pub time: i32,
+    pub psychic: String,
+    pub reported: Vec<String>,
+}
+
+pub struct Purchase {
+    pub time: i32,
+    pub purchaser: String,
+    pub color: &'static str,
+    pub item: String,
+}
+
+impl TimvExtension {
+    pub fn summary(
+        &self,
+        events: &[WrappedEvent],
+        teams: &[Team],
+        player_teams: &PlayerTeamMap,
+    ) -> TimvSummary {
+        // Roles are the teams players ended the game in
+        let mut players: Vec<RoleSummary> = teams
+            .iter()
+            .flat_map(|t| t.players.iter())
+            .map(|p| {
+                let team = player_teams
+                    .get_team_at(p.name, usize::MAX)
+                    .unwrap_or(&SPECTATORS);
+                RoleSummary {
+                    name: p.name.to_string(),
+                    role: team.name.to_string(),
+                    color: team.color,
+                    kills: vec![],
+                    tests: 0,
+                    spoofed: false,
+                    killed_by: None,
+                    death_cause: None,
+                    identified_by: None,
+                }
+            })
+            .collect();
+        let mut identifications = vec![];
+        let mut psychic_reports = vec![];
+        let mut purchases = vec![];
+
+        let color = |player: &str| -> &'static str {
+            player_teams
+                .get_team_at(player, usize::MAX)
+                .map(|t| t.color)
+                .unwrap_or(SPECTATORS.color)
+        };
+        for event in events {
+            match &event.event {
+                EventType::TimvTest(test) => {
+                    if let Some(player) = players.iter_mut().find(|p| p.name == test.get_player()) {
+                        player.tests += 1;
+                        player.spoofed |= test.get_spoofed();
+                    }
+                }
+                EventType::TimvDeath(death) => {
+                    if let Some(player) = players.iter_mut().find(|p| p.name == death.get_player())
+                    {
+                        player.death_cause = Some(death.get_damage_desc());
+                        player.killed_by =
+                            death.has_killer().then(|| death.get_killer().to_string());
+                    }
+                    if death.has_killer() {
+                        if let Some(killer) =
+                            players.iter_mut().find(|p| p.name == death.get_killer())
+                        {
+                            killer.kills.push(Kill {
+                                victim: death.get_player().to_string(),
+                                color: color(death.get_player()),
+                                assassin_target: death.get_assassin_target(),
+                            });
+                        }
+                    }
+                }
+                EventType::TimvBody(body) => {
+                    identifications.push(Identification {
+                        time: event.time,
+                        body: body.get_player().to_string(),
+                        body_color: color(body.get_player()),
+                        identifier: body.get_identifier().to_string(),
+                        identifier_color: color(body.get_identifier()),
+                        detective: false,
+                    });
+                    if let Some(player) = players.iter_mut().find(|p| p.name == body.get_player()) {
+                        player
+                            .identified_by
+                            .get_or_insert_with(|| body.get_identifier().to_string());
+                    }
+                }
+                EventType::TimvDetectiveBody(body) => {
+                    identifications.push(Identification {
+                        time: event.time,
+                        body: body.get_player().to_string(),
+                        body_color: color(body.get_player()),
+                        identifier: body.get_identifier().to_string(),
+                        identifier_color: color(body.get_identifier()),
+                        detective: true,
+                    });
+                }
+                EventType::TimvPsychicReport(report) => psychic_reports.push(PsychicReport {
+                    time: event.time,
+                    psychic: report.get_psychic().to_string(),
+                    reported: report.get_reported().to_vec(),
+                }),
+                EventType::TimvSharedPurchase(purchase) => purchases.push(Purchase {
+                    time: event.time,
+                    purchaser: purchase.get_purchaser().to_string(),
+                    color: color(purchase.get_purchaser()),
+                    item: purchase.get_item().to_string(),
+                }),
+                _ => {}
+            }
+        }
+        TimvSummary {
+            players,
+            identifications,
+            psychic_reports,
+            purchases,
+        }
+    }
+
+    /// Narrates who the traitors were, the top killer and body identifications
+    pub fn recap(&self, summary: &TimvSummary, events: &[WrappedEvent]) -> Vec<String> {
+        let mut lines = vec![];
+        let traitors: Vec<&str> = summary
+            .players
+            .iter()
+            .filter(|p| p.role.eq_ignore_ascii_case("traitor"))
+            .map(|p| p.name.as_str())
+            .collect();
+        match traitors.len() {
+            0 => {}
+            1 => lines.push(format!("{} was the traitor", traitors[0])),
+            _ => lines.push(format!(
+                "{} were the traitors",
+                recap::join_names(traitors.into_iter())
+            )),
+        }
+        lines.extend(recap::top_clause(
+            summary
+                .players
+                .iter()
+                .map(|p| (p.name.as_str(), p.kills.len() as u32)),
+            |n| format!("got {}", recap::plural(n, "kill")),
+        ));
+        match summary.identifications.len() {
+            0 => {}
+            1 => lines.push(String::from("1 body was identified")),
+            n => lines.push(format!("{} bodies were identified", n)),
+        }
+        if let Some(last) = events.iter().rev().find(|e| e.event.kill().is_some()) {
+            lines.push(recap::decided_at(last.time));
+        }
+        lines
+    }
+}
+
 impl GameLogExtension for TimvExtension {
     fn get_box_color(&self, event: &super::EventType) -> &'static str {
         match event {
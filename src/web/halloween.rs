@@ -0,0 +1,162 @@
+// Copyright (C) 2021 RoccoDev
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{
+    error::{Error, Result},
+    modes::GameMode,
+    web::{gamelog::index, get_current_year},
+    AppState,
+};
+use actix_web::{
+    http::header::{ContentType, IntoHeaderValue},
+    web, HttpResponse,
+};
+use askama::Template;
+use cached::{proc_macro::cached, Cached, TimedCache};
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+
+const LEADERBOARD_SIZE: usize = 10;
+
+#[derive(Template)]
+#[template(path = "halloween/edition.html")]
+struct EditionTemplate {
+    edition: EditionStats,
+    current_year: String,
+}
+
+#[derive(Template)]
+#[template(path = "halloween/editions.html")]
+struct EditionsTemplate {
+    editions: Vec<EditionStats>,
+    current_year: String,
+}
+
+/// Statistics of a single Halloween event edition
+#[derive(Clone)]
+pub struct EditionStats {
+    pub mode: GameMode,
+    pub year: u16,
+    pub games: usize,
+    pub players: usize,
+    pub kills: u32,
+    pub top_killers: Vec<Entry>,
+    pub most_played: Vec<Entry>,
+    pub most_wins: Vec<Entry>,
+}
+
+#[derive(Clone)]
+pub struct Entry {
+    pub name: String,
+    pub value: u32,
+}
+
+pub async fn halloween_editions(state: web::Data<AppState>) -> Result<HttpResponse> {
+    let mut editions = vec![];
+    for mode in GameMode::halloween_editions() {
+        editions.push(edition_stats(state.clone(), mode).await?);
+    }
+    let render = EditionsTemplate {
+        editions,
+        current_year: get_current_year(),
+    }
+    .render()
+    .unwrap();
+    Ok(HttpResponse::Ok()
+        .content_type(IntoHeaderValue::try_into(ContentType::html()).unwrap())
+        .body(render))
+}
+
+pub async fn halloween_edition(
+    state: web::Data<AppState>,
+    web::Path(year): web::Path<u16>,
+) -> Result<HttpResponse> {
+    let mode = GameMode::halloween_editions()
+        .find(|m| m.halloween_year() == Some(year))
+        .ok_or(Error::NotFound)?;
+    let render = EditionTemplate {
+        edition: edition_stats(state, mode).await?,
+        current_year: get_current_year(),
+    }
+    .render()
+    .unwrap();
+    Ok(HttpResponse::Ok()
+        .content_type(IntoHeaderValue::try_into(ContentType::html()).unwrap())
+        .body(render))
+}
+
+#[cached(
+    ty = "TimedCache<GameMode, EditionStats>",
+    create = "{ TimedCache::with_lifespan(Duration::from_secs(900)) }",
+    convert = "{ mode }",
+    result
+)]
+async fn edition_stats(state: web::Data<AppState>, mode: GameMode) -> Result<EditionStats> {
+    // The stored pages are public: hidden games are left out and nicked players only appear
+    // by their nick
+    let games = index::mode_games(state, mode).await?;
+    let mut kills: HashMap<String, u32> = HashMap::new();
+    let mut played: HashMap<String, u32> = HashMap::new();
+    let mut wins: HashMap<String, u32> = HashMap::new();
+
+    for game in games.iter() {
+        let players: HashSet<&str> = game.players.iter().map(|p| p.name.as_str()).collect();
+        for player in &players {
+            *played.entry(player.to_string()).or_default() += 1;
+        }
+        let winners: HashSet<&str> = game
+            .players
+            .iter()
+            .filter(|p| p.won)
+            .map(|p| p.name.as_str())
+            .collect();
+        for winner in &winners {
+            *wins.entry(winner.to_string()).or_default() += 1;
+        }
+        for kill in &game.kills {
+            if let Some(killer) = game.player(&kill.killer) {
+                *kills.entry(killer.name.clone()).or_default() += 1;
+            }
+        }
+    }
+
+    Ok(EditionStats {
+        mode,
+        year: mode.halloween_year().unwrap_or_default(),
+        games: games.len(),
+        players: played.len(),
+        kills: kills.values().sum(),
+        top_killers: leaderboard(kills),
+        most_played: leaderboard(played),
+        most_wins: leaderboard(wins),
+    })
+}
+
+/// Drops the cached statistics of an edition
+pub(super) async fn forget_edition(mode: GameMode) {
+    EDITION_STATS.lock().await.cache_remove(&mode);
+}
+
+fn leaderboard(values: HashMap<String, u32>) -> Vec<Entry> {
+    let mut entries: Vec<Entry> = values
+        .into_iter()
+        .map(|(name, value)| Entry { name, value })
+        .collect();
+    entries.sort_by(|a, b| b.value.cmp(&a.value).then_with(|| a.name.cmp(&b.name)));
+    entries.truncate(LEADERBOARD_SIZE);
+    entries
+}
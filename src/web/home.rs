@@ -0,0 +1,167 @@
+// Copyright (C) 2021 RoccoDev
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{
+    db::PublicStats,
+    error::Result,
+    modes::GameMode,
+    web::{
+        gamelog::{index::IndexedGame, parse_mode},
+        get_current_year,
+    },
+    AppState,
+};
+use actix_web::{
+    http::header::{ContentType, IntoHeaderValue},
+    web, HttpResponse,
+};
+use askama::Template;
+use cached::{proc_macro::cached, Cached, TimedCache};
+use std::{collections::HashSet, sync::Arc, time::Duration};
+use strum::IntoEnumIterator;
+
+const HOME_RECENT_GAMES: usize = 5;
+const MODE_RECENT_GAMES: usize = 20;
+
+#[derive(Template)]
+#[template(path = "home.html")]
+struct HomeTemplate<'a> {
+    modes: Vec<ModeOverview<'a>>,
+    total_games: usize,
+    total_players: usize,
+    current_year: String,
+}
+
+struct ModeOverview<'a> {
+    mode: GameMode,
+    games: usize,
+    recent: Vec<&'a IndexedGame>,
+}
+
+#[derive(Template)]
+#[template(path = "mode.html")]
+struct ModeTemplate<'a> {
+    mode: GameMode,
+    games: usize,
+    players: usize,
+    maps: Vec<MapOverview>,
+    recent: Vec<&'a IndexedGame>,
+    current_year: String,
+}
+
+struct MapOverview {
+    name: String,
+    slug: String,
+    games: u32,
+}
+
+/// Totals and latest games of a mode, for the home and mode pages
+struct ModeSummary {
+    stats: PublicStats,
+    recent: Vec<IndexedGame>,
+}
+
+#[cached(
+    ty = "TimedCache<GameMode, Arc<ModeSummary>>",
+    create = "{ TimedCache::with_lifespan(Duration::from_secs(900)) }",
+    convert = "{ mode }",
+    result,
+    sync_writes = "default"
+)]
+async fn mode_summary(state: web::Data<AppState>, mode: GameMode) -> Result<Arc<ModeSummary>> {
+    Ok(Arc::new(ModeSummary {
+        stats: state.db.public_stats(mode).await?,
+        recent: state
+            .db
+            .public_games(mode, &[], Some(MODE_RECENT_GAMES as i64))
+            .await?,
+    }))
+}
+
+/// Drops the cached summary of a mode, after staff hid, showed or deleted one of its games
+pub(super) async fn forget_mode(mode: GameMode) {
+    MODE_SUMMARY.lock().await.cache_remove(&mode);
+}
+
+pub async fn home(state: web::Data<AppState>) -> Result<HttpResponse> {
+    let mut summaries = vec![];
+    for mode in GameMode::iter() {
+        summaries.push((mode, mode_summary(state.clone(), mode).await?));
+    }
+    let players: HashSet<&str> = summaries
+        .iter()
+        .flat_map(|(_, summary)| summary.stats.players.iter())
+        .map(|uuid| uuid.as_str())
+        .collect();
+    let render = HomeTemplate {
+        modes: summaries
+            .iter()
+            .filter(|(_, summary)| summary.stats.games > 0)
+            .map(|(mode, summary)| ModeOverview {
+                mode: *mode,
+                games: summary.stats.games as usize,
+                recent: summary.recent.iter().take(HOME_RECENT_GAMES).collect(),
+            })
+            .collect(),
+        total_games: summaries
+            .iter()
+            .map(|(_, summary)| summary.stats.games as usize)
+            .sum(),
+        total_players: players.len(),
+        current_year: get_current_year(),
+    }
+    .render()
+    .unwrap();
+    Ok(HttpResponse::Ok()
+        .content_type(IntoHeaderValue::try_into(ContentType::html()).unwrap())
+        .body(render))
+}
+
+pub async fn mode_page(
+    state: web::Data<AppState>,
+    web::Path(mode): web::Path<String>,
+) -> Result<HttpResponse> {
+    let mode = parse_mode(mode)?;
+    let summary = mode_summary(state.clone(), mode).await?;
+
+    // Gravity games are played on several stages, which are counted instead
+    let mut maps: Vec<MapOverview> = vec![];
+    for (name, games) in &summary.stats.maps {
+        let slug = state.maps.slug(mode, name);
+        match maps.iter_mut().find(|m| m.slug == slug) {
+            Some(map) => map.games += games,
+            None => maps.push(MapOverview {
+                name: state.maps.get(mode, name).name,
+                slug,
+                games: *games,
+            }),
+        }
+    }
+    maps.sort_by(|a, b| b.games.cmp(&a.games).then_with(|| a.name.cmp(&b.name)));
+
+    let render = ModeTemplate {
+        mode,
+        games: summary.stats.games as usize,
+        players: summary.stats.players.len(),
+        maps,
+        recent: summary.recent.iter().collect(),
+        current_year: get_current_year(),
+    }
+    .render()
+    .unwrap();
+    Ok(HttpResponse::Ok()
+        .content_type(IntoHeaderValue::try_into(ContentType::html()).unwrap())
+        .body(render))
+}
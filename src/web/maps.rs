@@ -0,0 +1,248 @@
+// Copyright (C) 2021 RoccoDev
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{
+    error::{Error, Result},
+    maps::MapEntry,
+    modes::GameMode,
+    web::{
+        gamelog::{
+            index::{self, IndexedGame, IndexedStage},
+            parse_mode,
+        },
+        get_current_year,
+    },
+    AppState,
+};
+use actix_web::{
+    http::header::{ContentType, IntoHeaderValue},
+    web, HttpResponse,
+};
+use askama::Template;
+use std::collections::HashMap;
+
+const RECENT_GAMES: usize = 20;
+const LEADERBOARD_SIZE: usize = 10;
+
+#[derive(Template)]
+#[template(path = "map.html")]
+struct MapTemplate<'a> {
+    mode: GameMode,
+    map: MapEntry<'a>,
+    games: usize,
+    average_duration: i32,
+    recent: Vec<RecentGame>,
+    teams: Vec<TeamRecord>,
+    winners: Vec<Entry>,
+    stage: Option<StageStats>,
+    current_year: String,
+}
+
+struct RecentGame {
+    id: String,
+    start: i64,
+    duration: i32,
+    winners: String,
+}
+
+struct TeamRecord {
+    name: String,
+    played: u32,
+    won: u32,
+}
+
+struct Entry {
+    name: String,
+    value: u32,
+}
+
+/// Statistics of a Gravity stage over every game that included it
+struct StageStats {
+    players: u32,
+    completions: usize,
+    skips: u32,
+    hardcore_fails: u32,
+    best: Option<BestTime>,
+    median: Option<u64>,
+}
+
+struct BestTime {
+    player: String,
+    time: u64,
+}
+
+impl TeamRecord {
+    fn win_rate(&self) -> String {
+        format!(
+            "{:.0}%",
+            self.won as f32 * 100.0 / self.played.max(1) as f32
+        )
+    }
+}
+
+impl StageStats {
+    fn completion_rate(&self) -> String {
+        format!(
+            "{:.0}%",
+            self.completions as f32 * 100.0 / self.players.max(1) as f32
+        )
+    }
+}
+
+pub async fn map_page(
+    state: web::Data<AppState>,
+    web::Path((mode, slug)): web::Path<(String, String)>,
+) -> Result<HttpResponse> {
+    let mode = parse_mode(mode)?;
+    let all_games = index::mode_games(state.clone(), mode).await?;
+
+    // Gravity games are played on several stages, which get a page each
+    let slug = state.maps.slug(mode, &slug);
+    let matches = |name: &str| state.maps.slug(mode, name) == slug;
+    let games: Vec<&IndexedGame> = all_games
+        .iter()
+        .filter(|g| {
+            if mode == GameMode::GRAV {
+                g.stages.iter().any(|s| matches(&s.name))
+            } else {
+                matches(&g.map)
+            }
+        })
+        .collect();
+    let latest = games.first().ok_or(Error::NotFound)?;
+    let map = state.maps.get(
+        mode,
+        if mode == GameMode::GRAV {
+            latest
+                .stages
+                .iter()
+                .find(|s| matches(&s.name))
+                .map_or("", |s| s.name.as_str())
+        } else {
+            &latest.map
+        },
+    );
+
+    let mut teams: Vec<TeamRecord> = vec![];
+    let mut winners: HashMap<&str, (&str, u32)> = HashMap::new();
+    for game in &games {
+        if game.has_teams() {
+            let mut seen: Vec<&str> = vec![];
+            for player in &game.players {
+                if seen.contains(&player.team.as_str()) {
+                    continue;
+                }
+                seen.push(&player.team);
+                let record = match teams.iter_mut().find(|t| t.name == player.team) {
+                    Some(record) => record,
+                    None => {
+                        teams.push(TeamRecord {
+                            name: player.team.clone(),
+                            played: 0,
+                            won: 0,
+                        });
+                        teams.last_mut().unwrap()
+                    }
+                };
+                record.played += 1;
+                record.won += player.won as u32;
+            }
+        }
+        // Games are newest first, so players are shown with their latest name
+        for player in game.players.iter().filter(|p| p.won) {
+            winners.entry(&player.uuid).or_insert((&player.name, 0)).1 += 1;
+        }
+    }
+    teams.sort_by(|a, b| b.played.cmp(&a.played).then_with(|| a.name.cmp(&b.name)));
+    let mut winners: Vec<Entry> = winners
+        .into_values()
+        .map(|(name, value)| Entry {
+            name: name.to_string(),
+            value,
+        })
+        .collect();
+    winners.sort_by(|a, b| b.value.cmp(&a.value).then_with(|| a.name.cmp(&b.name)));
+    winners.truncate(LEADERBOARD_SIZE);
+
+    let stage = (mode == GameMode::GRAV).then(|| {
+        stage_stats(
+            games
+                .iter()
+                .flat_map(|g| g.stages.iter())
+                .filter(|s| matches(&s.name)),
+        )
+    });
+
+    let render = MapTemplate {
+        mode,
+        map,
+        games: games.len(),
+        average_duration: (games.iter().map(|g| g.duration as i64).sum::<i64>()
+            / games.len() as i64) as i32,
+        recent: games
+            .iter()
+            .take(RECENT_GAMES)
+            .map(|g| RecentGame {
+                id: g.id.clone(),
+                start: g.start,
+                duration: g.duration,
+                winners: g.winners.join(", "),
+            })
+            .collect(),
+        teams,
+        winners,
+        stage,
+        current_year: get_current_year(),
+    }
+    .render()
+    .unwrap();
+    Ok(HttpResponse::Ok()
+        .content_type(IntoHeaderValue::try_into(ContentType::html()).unwrap())
+        .body(render))
+}
+
+fn stage_stats<'a>(stages: impl Iterator<Item = &'a IndexedStage>) -> StageStats {
+    let mut stats = StageStats {
+        players: 0,
+        completions: 0,
+        skips: 0,
+        hardcore_fails: 0,
+        best: None,
+        median: None,
+    };
+    let mut times = vec![];
+    for stage in stages {
+        stats.players += stage.players;
+        stats.skips += stage.skips;
+        stats.hardcore_fails += stage.hardcore_fails;
+        for (player, time) in &stage.completions {
+            times.push(*time);
+            if stats.best.as_ref().is_none_or(|best| *time < best.time) {
+                stats.best = Some(BestTime {
+                    player: player.clone(),
+                    time: *time,
+                });
+            }
+        }
+    }
+    times.sort_unstable();
+    stats.completions = times.len();
+    stats.median = times.get(times.len() / 2).copied();
+    stats
+}
+
+mod filters {
+    pub use crate::web::gamelog::filters::*;
+}
@@ -14,15 +14,102 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use actix_files::Files;
-use actix_web::{web, Scope};
+use actix_web::{web, HttpRequest, Scope};
 use cached::proc_macro::once;
-use std::time::Duration;
+use std::{net::IpAddr, time::Duration};
 use time::OffsetDateTime;
 
+mod anomalies;
+mod chat;
+mod compare;
 mod gamelog;
+mod halloween;
+mod home;
+mod maps;
+mod moderation;
+mod reports;
+mod search;
+mod staff;
+
+pub use gamelog::{encode_game_id, game_summary, index_logs, parse_game_path};
+pub use staff::StaffSessions;
+
+lazy_static::lazy_static! {
+    /// Public address of the site, for links shared outside of it, from `KIG_SITE_URL`
+    static ref SITE_URL: String = std::env::var("KIG_SITE_URL")
+        .unwrap_or_else(|_| String::from("https://playkig.com"))
+        .trim_end_matches('/')
+        .to_string();
+    /// Reverse proxies allowed to tell the address of visitors, from the comma-separated
+    /// `KIG_TRUSTED_PROXIES` (loopback by default)
+    static ref TRUSTED_PROXIES: Vec<IpAddr> = std::env::var("KIG_TRUSTED_PROXIES")
+        .unwrap_or_else(|_| String::from("127.0.0.1,::1"))
+        .split(',')
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(|p| p.parse().unwrap_or_else(|_| panic!("invalid trusted proxy {}", p)))
+        .collect();
+}
 
 pub fn add_routes() -> Scope {
-    web::scope("/").route("/game/{mode}/{id}", web::get().to(gamelog::gamelog_by_id))
+    web::scope("/")
+        .route("", web::get().to(home::home))
+        .route("/search", web::get().to(search::search))
+        .route("/g/{id}", web::get().to(gamelog::short_link))
+        .route("/game/{mode}", web::get().to(home::mode_page))
+        .route("/game/{mode}/{id}", web::get().to(gamelog::gamelog_by_id))
+        .route("/oembed", web::get().to(gamelog::oembed))
+        .route(
+            "/game/{mode}/{id}/report",
+            web::post().to(reports::report_event),
+        )
+        .route(
+            "/game/{mode}/{id}/card.png",
+            web::get().to(gamelog::card_png),
+        )
+        .route(
+            "/game/{mode}/{id}/chart.svg",
+            web::get().to(gamelog::chart_svg),
+        )
+        .route(
+            "/compare/{player_a}/{player_b}",
+            web::get().to(compare::compare),
+        )
+        .route("/map/{mode}/{map}", web::get().to(maps::map_page))
+        .route("/halloween", web::get().to(halloween::halloween_editions))
+        .route(
+            "/halloween/{year}",
+            web::get().to(halloween::halloween_edition),
+        )
+        .route("/staff", web::get().to(staff::login_page))
+        .route("/staff/login", web::get().to(staff::login_page))
+        .route("/staff/login", web::post().to(staff::login))
+        .route("/staff/logout", web::post().to(staff::logout))
+        .route(
+            "/staff/anomalies/grav",
+            web::get().to(anomalies::grav_anomalies),
+        )
+        .route("/staff/search", web::get().to(search::log_search))
+        .route("/staff/chat", web::get().to(chat::chat_search))
+        .route("/staff/search.json", web::get().to(search::log_search_json))
+        .route("/staff/audit", web::get().to(moderation::audit_log))
+        .route("/staff/reports", web::get().to(reports::report_queue))
+        .route(
+            "/staff/reports/{id}",
+            web::post().to(reports::triage_report),
+        )
+        .route(
+            "/staff/game/{mode}/{id}/hide",
+            web::post().to(moderation::hide_game),
+        )
+        .route(
+            "/staff/game/{mode}/{id}/unhide",
+            web::post().to(moderation::unhide_game),
+        )
+        .route(
+            "/staff/game/{mode}/{id}/delete",
+            web::post().to(moderation::delete_game),
+        )
 }
 
 pub fn static_files() -> Files {
@@ -41,8 +128,75 @@ pub fn images_fallback() -> Files {
     Files::new("/game-img", "img")
 }
 
+/// The address of the visitor. Requests relayed by a trusted proxy are attributed to the last
+/// untrusted address in `X-Forwarded-For`, which visitors cannot forge.
+fn client_address(req: &HttpRequest) -> IpAddr {
+    let mut address = match req.peer_addr() {
+        Some(peer) => peer.ip(),
+        None => return IpAddr::from([0, 0, 0, 0]),
+    };
+    let forwarded: Vec<&str> = req
+        .headers()
+        .get_all("x-forwarded-for")
+        .filter_map(|h| h.to_str().ok())
+        .flat_map(|h| h.split(','))
+        .collect();
+    // Each proxy appends the address it received the request from
+    for hop in forwarded.iter().rev() {
+        if !TRUSTED_PROXIES.contains(&address) {
+            break;
+        }
+        match hop.trim().parse() {
+            Ok(hop) => address = hop,
+            Err(_) => break,
+        }
+    }
+    address
+}
+
+/// A trimmed form field, or `None` if it is blank
+pub(super) fn non_empty(field: &str) -> Option<String> {
+    Some(field.trim()).filter(|f| !f.is_empty()).map(Into::into)
+}
+
+/// [`SITE_URL`], for templates
+fn site_url() -> &'static str {
+    &SITE_URL
+}
+
 #[once(time = 3600)]
 fn get_current_year() -> String {
     let time = OffsetDateTime::now_local().unwrap_or_else(|_| OffsetDateTime::now_utc());
     time.year().to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::client_address;
+    use actix_web::test::TestRequest;
+    use std::net::IpAddr;
+
+    fn address(peer: &str, forwarded: &str) -> IpAddr {
+        let req = TestRequest::default()
+            .peer_addr(peer.parse().unwrap())
+            .header("x-forwarded-for", forwarded)
+            .to_http_request();
+        client_address(&req)
+    }
+
+    #[test]
+    fn client_address_ignores_forwarded_for_from_visitors() {
+        assert_eq!(
+            address("203.0.113.7:5000", "198.51.100.1"),
+            IpAddr::from([203, 0, 113, 7])
+        );
+    }
+
+    #[test]
+    fn client_address_skips_forged_hops_behind_a_proxy() {
+        assert_eq!(
+            address("127.0.0.1:5000", "198.51.100.1, 203.0.113.7"),
+            IpAddr::from([203, 0, 113, 7])
+        );
+    }
+}
@@ -0,0 +1,191 @@
+// Copyright (C) 2021 RoccoDev
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{
+    auth::Role,
+    db::{AuditEntry, DbHandle, HiddenLog},
+    error::{Error, Result},
+    modes::GameMode,
+    web::{
+        gamelog::{encode_game_id, forget_log, parse_game_path},
+        get_current_year,
+        staff::Staff,
+    },
+    AppState,
+};
+use actix_web::{
+    http::header::{self, ContentType, IntoHeaderValue},
+    web, HttpResponse,
+};
+use askama::Template;
+use serde::Deserialize;
+
+/// Maximum amount of actions shown in the audit log
+const MAX_ENTRIES: i64 = 200;
+
+#[derive(Deserialize)]
+pub struct ModerationForm {
+    #[serde(default)]
+    reason: String,
+}
+
+#[derive(Template)]
+#[template(path = "audit.html")]
+struct AuditTemplate {
+    entries: Vec<AuditRow>,
+    current_year: String,
+}
+
+struct AuditRow {
+    entry: AuditEntry,
+    /// Page the action applied to, if it still exists
+    link: Option<String>,
+}
+
+/// Hides a game from public view. Staff can still see it.
+pub async fn hide_game(
+    staff: Staff,
+    state: web::Data<AppState>,
+    web::Path((mode, path_id)): web::Path<(String, String)>,
+    form: web::Form<ModerationForm>,
+) -> Result<HttpResponse> {
+    staff.require(Role::Moderator)?;
+    let reason = reason(&form)?;
+    let (mode, id) = parse_game_path(mode, &path_id)?;
+    let hidden = HiddenLog {
+        reason: reason.clone(),
+        by: staff.0.name.clone(),
+        at: DbHandle::now(),
+    };
+    if !state
+        .db
+        .set_game_log_hidden(mode.get_database_id(), id.clone(), Some(&hidden))
+        .await?
+    {
+        return Err(Error::NotFound);
+    }
+    audit(&state, &staff, "hide", mode, &id, reason).await?;
+    Ok(redirect(&game_path(mode, &id)))
+}
+
+/// Shows a hidden game again
+pub async fn unhide_game(
+    staff: Staff,
+    state: web::Data<AppState>,
+    web::Path((mode, path_id)): web::Path<(String, String)>,
+    form: web::Form<ModerationForm>,
+) -> Result<HttpResponse> {
+    staff.require(Role::Moderator)?;
+    let reason = reason(&form)?;
+    let (mode, id) = parse_game_path(mode, &path_id)?;
+    if !state
+        .db
+        .set_game_log_hidden(mode.get_database_id(), id.clone(), None)
+        .await?
+    {
+        return Err(Error::NotFound);
+    }
+    audit(&state, &staff, "unhide", mode, &id, reason).await?;
+    Ok(redirect(&game_path(mode, &id)))
+}
+
+/// Deletes a game permanently
+pub async fn delete_game(
+    staff: Staff,
+    state: web::Data<AppState>,
+    web::Path((mode, path_id)): web::Path<(String, String)>,
+    form: web::Form<ModerationForm>,
+) -> Result<HttpResponse> {
+    staff.require(Role::Admin)?;
+    let reason = reason(&form)?;
+    let (mode, id) = parse_game_path(mode, &path_id)?;
+    if !state
+        .db
+        .delete_game_log(mode.get_database_id(), id.clone())
+        .await?
+    {
+        return Err(Error::NotFound);
+    }
+    audit(&state, &staff, "delete", mode, &id, reason).await?;
+    Ok(redirect("/staff/audit"))
+}
+
+/// The most recent staff actions
+pub async fn audit_log(staff: Staff, state: web::Data<AppState>) -> Result<HttpResponse> {
+    staff.require(Role::Moderator)?;
+    let entries = state
+        .db
+        .audit_entries(MAX_ENTRIES)
+        .await?
+        .into_iter()
+        .map(|entry| AuditRow {
+            link: match entry.action.as_str() {
+                "hide" | "unhide" | "erase" => Some(format!("/game/{}", entry.target)),
+                _ => None,
+            },
+            entry,
+        })
+        .collect();
+
+    let render = AuditTemplate {
+        entries,
+        current_year: get_current_year(),
+    }
+    .render()
+    .unwrap();
+    Ok(HttpResponse::Ok()
+        .content_type(IntoHeaderValue::try_into(ContentType::html()).unwrap())
+        .body(render))
+}
+
+fn reason(form: &ModerationForm) -> Result<String> {
+    Some(form.reason.trim())
+        .filter(|r| !r.is_empty())
+        .map(Into::into)
+        .ok_or(Error::MissingReason)
+}
+
+/// Records the action and drops the cached copies of the game, so it takes effect right away
+async fn audit(
+    state: &AppState,
+    staff: &Staff,
+    action: &str,
+    mode: GameMode,
+    id: &[u8],
+    reason: String,
+) -> Result<()> {
+    forget_log(mode, id).await;
+    state
+        .db
+        .add_audit_entry(&AuditEntry {
+            action: action.into(),
+            target: format!("{}/{}", mode.get_database_id(), encode_game_id(id)),
+            reason,
+            details: String::new(),
+            user: staff.0.name.clone(),
+            time: DbHandle::now(),
+        })
+        .await
+}
+
+fn game_path(mode: GameMode, id: &[u8]) -> String {
+    format!("/game/{}/{}", mode.get_database_id(), encode_game_id(id))
+}
+
+fn redirect(location: &str) -> HttpResponse {
+    HttpResponse::Found()
+        .header(header::LOCATION, location)
+        .finish()
+}
@@ -0,0 +1,279 @@
+// Copyright (C) 2021 RoccoDev
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{
+    auth::Role,
+    db::{DbHandle, Report, ReportStatus},
+    error::{Error, Result},
+    modes::GameMode,
+    web::{
+        client_address,
+        gamelog::{encode_game_id, get_log, is_reportable_event, parse_game_path, parse_mode},
+        get_current_year,
+        staff::Staff,
+        SITE_URL,
+    },
+    AppState,
+};
+use actix_web::{
+    client::Client,
+    http::header::{self, ContentType, IntoHeaderValue},
+    web, HttpRequest, HttpResponse,
+};
+use askama::Template;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    str::FromStr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use strum::IntoEnumIterator;
+
+/// Reports accepted from the same address within [`REPORT_WINDOW`]
+const MAX_REPORTS: usize = 5;
+const REPORT_WINDOW: Duration = Duration::from_secs(600);
+/// Longer reasons are cut
+const MAX_REASON_LENGTH: usize = 500;
+/// Maximum amount of reports shown in the queue
+const MAX_QUEUE: i64 = 100;
+
+lazy_static::lazy_static! {
+    /// Times of the recent reports of each address
+    static ref RECENT_REPORTS: Mutex<HashMap<IpAddr, Vec<Instant>>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Deserialize)]
+pub struct ReportForm {
+    event: u32,
+    #[serde(default)]
+    reason: String,
+}
+
+#[derive(Deserialize)]
+pub struct QueueQuery {
+    #[serde(default)]
+    status: String,
+}
+
+#[derive(Deserialize)]
+pub struct TriageForm {
+    status: String,
+}
+
+#[derive(Template)]
+#[template(path = "reports.html")]
+struct QueueTemplate {
+    status: ReportStatus,
+    statuses: Vec<ReportStatus>,
+    reports: Vec<QueuedReport>,
+    current_year: String,
+}
+
+struct QueuedReport {
+    id: String,
+    mode: GameMode,
+    game_id: String,
+    event: u32,
+    reason: String,
+    time: i64,
+    handled_by: Option<String>,
+}
+
+/// Sent to the report webhook. `content` is shown by Discord-compatible webhooks.
+#[derive(Serialize)]
+struct ReportNotification {
+    content: String,
+    mode: &'static str,
+    game: String,
+    event: u32,
+    reason: String,
+    url: String,
+    /// Keeps reasons from pinging roles or users on Discord
+    allowed_mentions: AllowedMentions,
+}
+
+#[derive(Serialize)]
+struct AllowedMentions {
+    parse: [&'static str; 0],
+}
+
+impl QueueTemplate {
+    /// Whether the queue lists the reports with the given status
+    fn is_shown(&self, status: &ReportStatus) -> bool {
+        *status == self.status
+    }
+}
+
+/// Reports a chat message or kill of a game, from the game page
+pub async fn report_event(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    web::Path((mode, path_id)): web::Path<(String, String)>,
+    form: web::Form<ReportForm>,
+) -> Result<HttpResponse> {
+    let (mode, id) = parse_game_path(mode, &path_id)?;
+    let (log, _) = get_log(state.clone(), mode, id.clone(), false).await?;
+    if !is_reportable_event(mode, &log, form.event as usize) {
+        return Err(Error::NotFound);
+    }
+    let reason: String = form.reason.trim().chars().take(MAX_REASON_LENGTH).collect();
+    if reason.is_empty() {
+        return Err(Error::MissingReason);
+    }
+    if !allow_report(client_address(&req)) {
+        return Err(Error::TooManyRequests);
+    }
+
+    let report = Report {
+        id: None,
+        game: mode.get_database_id().into(),
+        game_id: id,
+        event: form.event,
+        reason,
+        time: DbHandle::now(),
+        status: ReportStatus::Open,
+        handled_by: None,
+    };
+    state.db.add_report(&report).await?;
+    actix_web::rt::spawn(notify(mode, path_id.clone(), report));
+    Ok(HttpResponse::Found()
+        .header(
+            header::LOCATION,
+            format!(
+                "/game/{}/{}?reported=1#event-{}",
+                mode.get_database_id(),
+                path_id,
+                form.event
+            ),
+        )
+        .finish())
+}
+
+/// Queue of player reports for moderators, by status
+pub async fn report_queue(
+    staff: Staff,
+    state: web::Data<AppState>,
+    query: web::Query<QueueQuery>,
+) -> Result<HttpResponse> {
+    staff.require(Role::Moderator)?;
+    let status = ReportStatus::from_str(&query.status).unwrap_or(ReportStatus::Open);
+    let reports = state
+        .db
+        .reports(status, MAX_QUEUE)
+        .await?
+        .into_iter()
+        .filter_map(|report| {
+            Some(QueuedReport {
+                id: report.id?,
+                mode: parse_mode(report.game).ok()?,
+                game_id: encode_game_id(&report.game_id),
+                event: report.event,
+                reason: report.reason,
+                time: report.time,
+                handled_by: report.handled_by,
+            })
+        })
+        .collect();
+
+    let render = QueueTemplate {
+        status,
+        statuses: ReportStatus::iter().collect(),
+        reports,
+        current_year: get_current_year(),
+    }
+    .render()
+    .unwrap();
+    Ok(HttpResponse::Ok()
+        .content_type(IntoHeaderValue::try_into(ContentType::html()).unwrap())
+        .body(render))
+}
+
+/// Marks a report as resolved or dismissed, or opens it again
+pub async fn triage_report(
+    staff: Staff,
+    state: web::Data<AppState>,
+    web::Path(id): web::Path<String>,
+    form: web::Form<TriageForm>,
+) -> Result<HttpResponse> {
+    staff.require(Role::Moderator)?;
+    let status = ReportStatus::from_str(&form.status).map_err(|_| Error::NotFound)?;
+    if !state
+        .db
+        .set_report_status(&id, status, &staff.0.name)
+        .await?
+    {
+        return Err(Error::NotFound);
+    }
+    Ok(HttpResponse::Found()
+        .header(header::LOCATION, "/staff/reports")
+        .finish())
+}
+
+/// Counts a report from an address, unless it sent too many recently
+fn allow_report(address: IpAddr) -> bool {
+    let now = Instant::now();
+    let mut recent = RECENT_REPORTS.lock().unwrap();
+    recent.retain(|_, times| {
+        times.retain(|t| now.duration_since(*t) < REPORT_WINDOW);
+        !times.is_empty()
+    });
+    let times = recent.entry(address).or_default();
+    if times.len() >= MAX_REPORTS {
+        return false;
+    }
+    times.push(now);
+    true
+}
+
+/// Sends a report to the URL in `KIG_REPORT_WEBHOOK`, if set
+async fn notify(mode: GameMode, game_id: String, report: Report) {
+    let webhook = match std::env::var("KIG_REPORT_WEBHOOK") {
+        Ok(webhook) => webhook,
+        Err(_) => return,
+    };
+    let url = format!(
+        "{}/game/{}/{}#event-{}",
+        *SITE_URL,
+        mode.get_database_id(),
+        game_id,
+        report.event
+    );
+    let notification = ReportNotification {
+        content: format!(
+            "New report on {} game {}: {}\n{}",
+            mode.get_full_name(),
+            game_id,
+            report.reason,
+            url
+        ),
+        mode: mode.get_database_id(),
+        game: game_id,
+        event: report.event,
+        reason: report.reason,
+        url,
+        allowed_mentions: AllowedMentions { parse: [] },
+    };
+    match Client::default()
+        .post(&webhook)
+        .send_json(&notification)
+        .await
+    {
+        Ok(res) if res.status().is_success() => {}
+        Ok(res) => eprintln!("Report webhook answered with {}", res.status()),
+        Err(e) => eprintln!("Could not send report webhook: {}", e),
+    }
+}
@@ -0,0 +1,235 @@
+// Copyright (C) 2021 RoccoDev
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{
+    db::GameLogFilter,
+    error::Result,
+    modes::GameMode,
+    web::{
+        gamelog::{
+            encode_game_id,
+            index::{self, IndexedGame},
+            parse_game_id, parse_mode,
+        },
+        get_current_year, non_empty,
+        staff::Staff,
+    },
+    AppState,
+};
+use actix_web::{
+    http::header::{self, ContentType, IntoHeaderValue},
+    web, HttpRequest, HttpResponse,
+};
+use askama::Template;
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+
+const MAX_GAMES: usize = 50;
+const MAX_PLAYERS: usize = 20;
+/// Maximum amount of games returned by a staff search
+const MAX_LOGS: usize = 100;
+
+#[derive(Template)]
+#[template(path = "search.html")]
+struct SearchTemplate {
+    query: String,
+    player: Option<String>,
+    games: Vec<IndexedGame>,
+    players: Vec<String>,
+    current_year: String,
+}
+
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    #[serde(default)]
+    q: String,
+}
+
+/// Finds a game by ID, or the games of a player by name
+pub async fn search(
+    state: web::Data<AppState>,
+    query: web::Query<SearchQuery>,
+) -> Result<HttpResponse> {
+    let query = query.into_inner().q.trim().to_string();
+
+    if let Ok(id) = parse_game_id(&query) {
+        for mode in GameMode::iter() {
+            if state.db.has_public_game(mode, id.clone()).await? {
+                return Ok(HttpResponse::Found()
+                    .header(
+                        header::LOCATION,
+                        format!("/game/{}/{}", mode.get_database_id(), query),
+                    )
+                    .finish());
+            }
+        }
+    }
+
+    let player = index::find_player(&state.db, &query).await?;
+    let mut games: Vec<IndexedGame> = vec![];
+    if let Some(player) = &player {
+        for mode in GameMode::iter() {
+            games.extend(
+                state
+                    .db
+                    .public_games(mode, &[&player.uuid], Some(MAX_GAMES as i64))
+                    .await?,
+            );
+        }
+    }
+    games.sort_by_key(|g| std::cmp::Reverse(g.start));
+    games.truncate(MAX_GAMES);
+
+    // Other players whose name starts with the query
+    let mut players: Vec<String> = vec![];
+    if !query.is_empty() {
+        for mode in GameMode::iter() {
+            players.extend(state.db.public_player_names(mode, &query).await?);
+        }
+    }
+    if let Some(player) = &player {
+        players.retain(|name| *name != player.name);
+    }
+    players.sort_by_key(|name| name.to_lowercase());
+    players.dedup();
+    players.truncate(MAX_PLAYERS);
+
+    let render = SearchTemplate {
+        query,
+        player: player.map(|p| p.name),
+        games,
+        players,
+        current_year: get_current_year(),
+    }
+    .render()
+    .unwrap();
+    Ok(HttpResponse::Ok()
+        .content_type(IntoHeaderValue::try_into(ContentType::html()).unwrap())
+        .body(render))
+}
+
+#[derive(Template)]
+#[template(path = "staff_search.html")]
+struct LogSearchTemplate<'a> {
+    query: &'a LogSearchQuery,
+    query_string: &'a str,
+    modes: Vec<GameMode>,
+    results: &'a [LogSearchResult],
+    current_year: String,
+}
+
+/// Filters of a staff search. Empty fields match every game.
+#[derive(Deserialize)]
+pub struct LogSearchQuery {
+    #[serde(default)]
+    mode: String,
+    /// Minimum game start, in milliseconds
+    #[serde(default)]
+    after: String,
+    /// Maximum game start, in milliseconds
+    #[serde(default)]
+    before: String,
+    #[serde(default)]
+    server: String,
+    #[serde(default)]
+    map: String,
+    #[serde(default)]
+    winner: String,
+    #[serde(default)]
+    player: String,
+}
+
+#[derive(Serialize)]
+pub struct LogSearchResult {
+    mode: &'static str,
+    id: String,
+    start: i64,
+    end: i64,
+    server: Option<String>,
+    maps: Vec<String>,
+    players: Vec<String>,
+    winners: Vec<String>,
+}
+
+/// Staff search over the indexed metadata of stored games
+pub async fn log_search(
+    _staff: Staff,
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    query: web::Query<LogSearchQuery>,
+) -> Result<HttpResponse> {
+    let results = search_logs(&state, &query).await?;
+    let render = LogSearchTemplate {
+        query: &query,
+        query_string: req.query_string(),
+        modes: GameMode::iter().collect(),
+        results: &results,
+        current_year: get_current_year(),
+    }
+    .render()
+    .unwrap();
+    Ok(HttpResponse::Ok()
+        .content_type(IntoHeaderValue::try_into(ContentType::html()).unwrap())
+        .body(render))
+}
+
+/// JSON version of [`log_search`]
+pub async fn log_search_json(
+    _staff: Staff,
+    state: web::Data<AppState>,
+    query: web::Query<LogSearchQuery>,
+) -> Result<HttpResponse> {
+    Ok(HttpResponse::Ok().json(search_logs(&state, &query).await?))
+}
+
+async fn search_logs(state: &AppState, query: &LogSearchQuery) -> Result<Vec<LogSearchResult>> {
+    let modes: Vec<GameMode> = match non_empty(&query.mode) {
+        Some(mode) => vec![parse_mode(mode)?],
+        None => GameMode::iter().collect(),
+    };
+    let filter = GameLogFilter {
+        after: query.after.parse().ok(),
+        before: query.before.parse().ok(),
+        server: non_empty(&query.server),
+        map: non_empty(&query.map),
+        winner: non_empty(&query.winner),
+        player: non_empty(&query.player),
+    };
+
+    let mut results = vec![];
+    for mode in modes {
+        let logs = state
+            .db
+            .search_game_logs(mode.get_database_id(), &filter, MAX_LOGS as i64)
+            .await?;
+        results.extend(logs.into_iter().map(|log| LogSearchResult {
+            mode: mode.get_database_id(),
+            id: encode_game_id(&log.id),
+            start: log.index.start,
+            end: log.index.end,
+            server: log.server,
+            maps: log.index.maps,
+            players: log.index.players,
+            winners: log.index.winners,
+        }));
+    }
+    results.sort_by_key(|r| std::cmp::Reverse(r.start));
+    results.truncate(MAX_LOGS);
+    Ok(results)
+}
+
+mod filters {
+    pub use crate::web::gamelog::filters::*;
+}
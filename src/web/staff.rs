@@ -0,0 +1,355 @@
+// Copyright (C) 2021 RoccoDev
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{
+    auth::{self, Role},
+    db::StaffUser,
+    error::{Error, Result},
+    web::{client_address, get_current_year},
+    AppState,
+};
+use actix_web::{
+    cookie::{Cookie, SameSite},
+    dev::{Payload, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::{self, ContentType, IntoHeaderValue},
+    web, FromRequest, HttpMessage, HttpRequest, HttpResponse,
+};
+use askama::Template;
+use futures::future::{ready, LocalBoxFuture, Ready};
+use serde::Deserialize;
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    net::IpAddr,
+    rc::Rc,
+    sync::Mutex,
+    task::{Context, Poll},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+const SESSION_COOKIE: &str = "kig_session";
+const SESSION_LIFETIME: Duration = Duration::from_secs(7 * 24 * 3600);
+/// Delay before answering a failed sign in, to slow down password guessing
+const FAILED_LOGIN_DELAY: Duration = Duration::from_secs(1);
+/// Failed sign-ins allowed within [`FAILED_LOGIN_WINDOW`] before further attempts are refused
+const MAX_FAILED_LOGINS_PER_ADDRESS: usize = 10;
+const MAX_FAILED_LOGINS_PER_ACCOUNT: usize = 5;
+const FAILED_LOGIN_WINDOW: Duration = Duration::from_secs(900);
+
+lazy_static::lazy_static! {
+    /// Times of the recent failed sign-ins from each address and for each account
+    static ref FAILED_LOGINS: Mutex<HashMap<LoginKey, Vec<Instant>>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Hash, PartialEq, Eq)]
+enum LoginKey {
+    Address(IpAddr),
+    /// Lowercase name of the account
+    Account(String),
+}
+
+impl LoginKey {
+    fn max_failures(&self) -> usize {
+        match self {
+            LoginKey::Address(_) => MAX_FAILED_LOGINS_PER_ADDRESS,
+            LoginKey::Account(_) => MAX_FAILED_LOGINS_PER_ACCOUNT,
+        }
+    }
+}
+
+/// Extractor for staff-only pages, requiring a signed-in staff member.
+///
+/// Any role can see staff pages. Handlers that need more call [`Staff::require`].
+pub struct Staff(pub StaffUser);
+
+impl Staff {
+    /// Fails unless the staff member has at least the given role
+    pub fn require(&self, role: Role) -> Result<()> {
+        if self.0.role >= role {
+            Ok(())
+        } else {
+            Err(Error::Forbidden)
+        }
+    }
+}
+
+impl FromRequest for Staff {
+    type Error = Error;
+    type Future = Ready<Result<Self>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(
+            req.extensions()
+                .get::<StaffUser>()
+                .cloned()
+                .map(Staff)
+                .ok_or(Error::Unauthorized),
+        )
+    }
+}
+
+/// Middleware resolving the staff member signed in on a request from their session cookie
+pub struct StaffSessions;
+
+pub struct StaffSessionsMiddleware<S> {
+    service: Rc<RefCell<S>>,
+}
+
+impl<S, B> Transform<S> for StaffSessions
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>
+        + 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Transform = StaffSessionsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<std::result::Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(StaffSessionsMiddleware {
+            service: Rc::new(RefCell::new(service)),
+        }))
+    }
+}
+
+impl<S, B> Service for StaffSessionsMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>
+        + 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, std::result::Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<std::result::Result<(), Self::Error>> {
+        self.service.borrow_mut().poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        Box::pin(async move {
+            let token = req.cookie(SESSION_COOKIE).map(|c| c.value().to_string());
+            let state = req.app_data::<web::Data<AppState>>();
+            if let (Some(token), Some(state)) = (token, state) {
+                match state.db.staff_session(&auth::token_hash(&token)).await {
+                    Ok(Some(user)) => {
+                        req.extensions_mut().insert(user);
+                    }
+                    Ok(None) => {}
+                    Err(e) => eprintln!("Could not check staff session: {}", e),
+                }
+            }
+            let res = service.borrow_mut().call(req);
+            res.await
+        })
+    }
+}
+
+#[derive(Template)]
+#[template(path = "login.html")]
+struct LoginTemplate<'a> {
+    user: Option<&'a StaffUser>,
+    next: &'a str,
+    failed: bool,
+    current_year: String,
+}
+
+#[derive(Deserialize)]
+pub struct LoginQuery {
+    #[serde(default)]
+    next: String,
+}
+
+#[derive(Deserialize)]
+pub struct LoginForm {
+    name: String,
+    password: String,
+    #[serde(default)]
+    next: String,
+}
+
+pub async fn login_page(staff: Option<Staff>, query: web::Query<LoginQuery>) -> HttpResponse {
+    render_login(staff.as_ref().map(|s| &s.0), &query.next, false)
+}
+
+pub async fn login(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    form: web::Form<LoginForm>,
+) -> Result<HttpResponse> {
+    let name = form.name.trim();
+    let keys = [
+        LoginKey::Address(client_address(&req)),
+        LoginKey::Account(name.to_lowercase()),
+    ];
+    if is_locked_out(&keys) {
+        return Err(Error::TooManyRequests);
+    }
+    let user = state.db.staff_user(name).await?;
+    let valid = match &user {
+        Some(user) => {
+            // Hashing is slow on purpose, so it runs off the server threads
+            let (password, hash) = (form.password.clone(), user.password.clone());
+            web::block(move || Ok::<_, ()>(auth::verify_password(&password, &hash)))
+                .await
+                .unwrap_or(false)
+        }
+        None => false,
+    };
+    let user = match user {
+        Some(user) if valid => user,
+        _ => {
+            record_failed_login(keys);
+            actix_web::rt::time::delay_for(FAILED_LOGIN_DELAY).await;
+            return Ok(render_login(None, &form.next, true));
+        }
+    };
+
+    let token = auth::session_token();
+    let expires = SystemTime::now() + SESSION_LIFETIME;
+    let expires = expires
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as i64);
+    state
+        .db
+        .create_staff_session(&auth::token_hash(&token), &user.name, expires)
+        .await?;
+    Ok(HttpResponse::Found()
+        .header(header::LOCATION, redirect_target(&form.next))
+        .cookie(
+            Cookie::build(SESSION_COOKIE, token)
+                .path("/")
+                .http_only(true)
+                .secure(true)
+                .same_site(SameSite::Lax)
+                .finish(),
+        )
+        .finish())
+}
+
+pub async fn logout(state: web::Data<AppState>, req: HttpRequest) -> Result<HttpResponse> {
+    let mut res = HttpResponse::Found();
+    res.header(header::LOCATION, "/");
+    if let Some(cookie) = req.cookie(SESSION_COOKIE) {
+        state
+            .db
+            .delete_staff_session(&auth::token_hash(cookie.value()))
+            .await?;
+        res.del_cookie(&Cookie::build(SESSION_COOKIE, "").path("/").finish());
+    }
+    Ok(res.finish())
+}
+
+/// Whether any of the keys failed to sign in too often within [`FAILED_LOGIN_WINDOW`]
+fn is_locked_out(keys: &[LoginKey]) -> bool {
+    let now = Instant::now();
+    let mut failures = FAILED_LOGINS.lock().unwrap();
+    failures.retain(|_, times| {
+        times.retain(|t| now.duration_since(*t) < FAILED_LOGIN_WINDOW);
+        !times.is_empty()
+    });
+    keys.iter()
+        .any(|key| failures.get(key).map_or(0, Vec::len) >= key.max_failures())
+}
+
+fn record_failed_login(keys: [LoginKey; 2]) {
+    let now = Instant::now();
+    let mut failures = FAILED_LOGINS.lock().unwrap();
+    for key in keys {
+        failures.entry(key).or_default().push(now);
+    }
+}
+
+fn render_login(user: Option<&StaffUser>, next: &str, failed: bool) -> HttpResponse {
+    let render = LoginTemplate {
+        user,
+        next,
+        failed,
+        current_year: get_current_year(),
+    }
+    .render()
+    .unwrap();
+    let mut res = if failed {
+        HttpResponse::Unauthorized()
+    } else {
+        HttpResponse::Ok()
+    };
+    res.content_type(IntoHeaderValue::try_into(ContentType::html()).unwrap())
+        .body(render)
+}
+
+/// Only redirects to pages of the site after signing in
+fn redirect_target(next: &str) -> &str {
+    if next.starts_with('/') && !next.starts_with("//") && !next.starts_with("/\\") {
+        next
+    } else {
+        "/staff"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        is_locked_out, record_failed_login, redirect_target, LoginKey,
+        MAX_FAILED_LOGINS_PER_ACCOUNT, MAX_FAILED_LOGINS_PER_ADDRESS,
+    };
+    use std::net::IpAddr;
+
+    #[test]
+    fn redirect_target_stays_on_the_site() {
+        assert_eq!(redirect_target("/staff/search?q=a"), "/staff/search?q=a");
+        for next in ["https://example.com", "//example.com", "/\\example.com", ""] {
+            assert_eq!(redirect_target(next), "/staff");
+        }
+    }
+
+    #[test]
+    fn failed_logins_lock_out_the_account_from_every_address() {
+        let account = || LoginKey::Account("locked-account".into());
+        for i in 0..MAX_FAILED_LOGINS_PER_ACCOUNT as u8 {
+            let address = LoginKey::Address(IpAddr::from([192, 0, 2, i]));
+            assert!(!is_locked_out(&[address, account()]));
+            record_failed_login([LoginKey::Address(IpAddr::from([192, 0, 2, i])), account()]);
+        }
+        let address = || LoginKey::Address(IpAddr::from([192, 0, 2, 255]));
+        assert!(is_locked_out(&[address(), account()]));
+        assert!(!is_locked_out(&[
+            address(),
+            LoginKey::Account("other-account".into())
+        ]));
+    }
+
+    #[test]
+    fn failed_logins_lock_out_the_address_for_every_account() {
+        let address = || LoginKey::Address(IpAddr::from([198, 51, 100, 1]));
+        for i in 0..MAX_FAILED_LOGINS_PER_ADDRESS {
+            record_failed_login([address(), LoginKey::Account(format!("account-{}", i))]);
+        }
+        assert!(is_locked_out(&[
+            address(),
+            LoginKey::Account("admin".into())
+        ]));
+        assert!(!is_locked_out(&[
+            LoginKey::Address(IpAddr::from([198, 51, 100, 2])),
+            LoginKey::Account("admin".into())
+        ]));
+    }
+}
@@ -0,0 +1,304 @@
+// Copyright (C) 2021 RoccoDev
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{
+    db::{DbHandle, WebhookDelivery},
+    modes::GameMode,
+};
+use actix_web::{
+    client::Client,
+    http::{header, StatusCode},
+};
+use hmac::{Hmac, Mac, NewMac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::{str::FromStr, sync::Arc, time::Duration};
+use tokio::sync::Semaphore;
+
+/// Attempts at delivering a payload before giving up
+const MAX_ATTEMPTS: u32 = 5;
+/// Delay before the first retry, doubled after each one
+const RETRY_DELAY: Duration = Duration::from_secs(10);
+/// Payloads delivered at the same time, the others wait for their turn
+const MAX_DELIVERIES: usize = 4;
+/// Header holding the HMAC-SHA256 of the payload, as `sha256=<hex>`
+const SIGNATURE_HEADER: &str = "X-Kig-Signature";
+
+/// Webhook settings, read from the TOML file at `KIG_WEBHOOKS` (`webhooks.toml` by default)
+#[derive(Deserialize, Default)]
+struct WebhookConfig {
+    #[serde(default)]
+    webhook: Vec<WebhookEntry>,
+}
+
+#[derive(Deserialize)]
+struct WebhookEntry {
+    url: String,
+    secret: String,
+    /// Database IDs of the modes whose games are sent, e.g. `cai`
+    modes: Vec<String>,
+}
+
+/// An endpoint notified of the finished games of some modes
+#[derive(Clone)]
+pub struct Webhook {
+    pub url: String,
+    /// Key used to sign the payloads
+    secret: String,
+    modes: Vec<GameMode>,
+}
+
+/// The configured webhooks
+pub struct Webhooks {
+    hooks: Vec<Webhook>,
+    deliveries: Arc<Semaphore>,
+}
+
+/// Summary of a finished game, sent to webhooks as JSON
+#[derive(Serialize)]
+pub struct GameSummary {
+    pub mode: &'static str,
+    /// Base62 ID of the game, as in its URL
+    pub id: String,
+    pub url: String,
+    /// Names of the winning players, or of the winning team. Unset for ties.
+    pub winner: Option<String>,
+    /// Map of the game, or Gravity stages in play order
+    pub map: String,
+    /// Start of the game, in milliseconds
+    pub start: i64,
+    /// In milliseconds
+    pub duration: i32,
+    pub players: Vec<SummaryPlayer>,
+}
+
+#[derive(Serialize)]
+pub struct SummaryPlayer {
+    pub name: String,
+    pub uuid: String,
+    pub team: String,
+    pub won: bool,
+}
+
+impl Webhooks {
+    /// Reads the webhooks from their file. Without one, no webhooks are sent.
+    pub fn load() -> Webhooks {
+        let path = std::env::var("KIG_WEBHOOKS").unwrap_or_else(|_| String::from("webhooks.toml"));
+        let config: WebhookConfig = match std::fs::read_to_string(&path) {
+            Ok(text) => toml::from_str(&text)
+                .unwrap_or_else(|e| panic!("Invalid webhooks in {}: {}", path, e)),
+            Err(_) => WebhookConfig::default(),
+        };
+        let hooks = config
+            .webhook
+            .into_iter()
+            .map(|entry| Webhook {
+                modes: entry
+                    .modes
+                    .iter()
+                    .map(|mode| {
+                        GameMode::from_str(&mode.to_uppercase()).unwrap_or_else(|_| {
+                            panic!("Unknown mode {} for webhook {}", mode, entry.url)
+                        })
+                    })
+                    .collect(),
+                url: entry.url,
+                secret: entry.secret,
+            })
+            .collect();
+        Webhooks {
+            hooks,
+            deliveries: Arc::new(Semaphore::new(MAX_DELIVERIES)),
+        }
+    }
+
+    /// The webhooks notified of the games of a mode
+    pub fn for_mode(&self, mode: GameMode) -> impl Iterator<Item = &Webhook> {
+        self.hooks.iter().filter(move |h| h.modes.contains(&mode))
+    }
+
+    /// Sends the summary of a finished game to the webhooks of its mode, in the background
+    pub fn game_finished(&self, db: Arc<DbHandle>, mode: GameMode, summary: GameSummary) {
+        let summary = Arc::new(summary);
+        for hook in self.for_mode(mode) {
+            let hook = hook.clone();
+            let db = db.clone();
+            let summary = summary.clone();
+            let deliveries = self.deliveries.clone();
+            actix_web::rt::spawn(async move {
+                let _permit = deliveries.acquire_owned().await;
+                deliver(&db, &hook, &summary).await;
+            });
+        }
+    }
+}
+
+/// Sends a summary to a webhook, retrying with a growing delay while the endpoint is
+/// unreachable or fails. Every attempt is recorded. Returns whether the summary was delivered.
+pub async fn deliver(db: &DbHandle, hook: &Webhook, summary: &GameSummary) -> bool {
+    let body = serde_json::to_vec(summary).unwrap();
+    let signature = format!("sha256={}", sign(&hook.secret, &body));
+    let mut delay = RETRY_DELAY;
+    for attempt in 1..=MAX_ATTEMPTS {
+        let (status, error, retry) = post(&hook.url, &body, &signature).await;
+        let delivery = WebhookDelivery {
+            url: hook.url.clone(),
+            game: summary.mode.into(),
+            game_id: summary.id.clone(),
+            attempt,
+            status: status.map(|s| s.as_u16()),
+            error,
+            time: DbHandle::now(),
+        };
+        if let Err(e) = db.add_webhook_delivery(&delivery).await {
+            eprintln!("Could not record webhook delivery: {}", e);
+        }
+        if delivery.error.is_empty() {
+            return true;
+        }
+        eprintln!(
+            "Could not deliver {}/{} to {}: {}",
+            summary.mode, summary.id, hook.url, delivery.error
+        );
+        if !retry || attempt == MAX_ATTEMPTS {
+            break;
+        }
+        actix_web::rt::time::delay_for(delay).await;
+        delay *= 2;
+    }
+    false
+}
+
+/// Posts a signed payload once. Returns the HTTP status of the answer, why the attempt failed
+/// (empty if it succeeded) and whether it is worth retrying.
+async fn post(url: &str, body: &[u8], signature: &str) -> (Option<StatusCode>, String, bool) {
+    let res = Client::default()
+        .post(url)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(SIGNATURE_HEADER, signature)
+        .send_body(body.to_vec())
+        .await;
+    match res {
+        Ok(res) if res.status().is_success() => (Some(res.status()), String::new(), false),
+        Ok(res) => (
+            Some(res.status()),
+            format!("Answered with {}", res.status()),
+            res.status().is_server_error() || res.status() == StatusCode::TOO_MANY_REQUESTS,
+        ),
+        Err(e) => (None, e.to_string(), true),
+    }
+}
+
+/// HMAC-SHA256 of a payload, in hex
+fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{post, sign, SIGNATURE_HEADER};
+    use actix_web::http::StatusCode;
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    #[test]
+    fn sign_matches_rfc_4231() {
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    /// Lowercase headers and body of a received request
+    type Received = (Vec<String>, Vec<u8>);
+
+    /// Answers one request with the given status. Returns the URL to send it to.
+    fn answer_once(status: &'static str) -> (String, thread::JoinHandle<Received>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut headers = vec![];
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim_end().is_empty() {
+                    break;
+                }
+                headers.push(line.trim_end().to_lowercase());
+            }
+            let length = headers
+                .iter()
+                .find_map(|h| h.strip_prefix("content-length: "))
+                .map_or(0, |l| l.parse().unwrap());
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            write!(
+                &stream,
+                "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                status
+            )
+            .unwrap();
+            (headers, body)
+        });
+        (url, handle)
+    }
+
+    #[test]
+    fn post_sends_signed_payload() {
+        let (url, server) = answer_once("200 OK");
+        let signature = format!("sha256={}", sign("secret", b"{}"));
+        let res = actix_web::rt::System::new("test").block_on({
+            let signature = signature.clone();
+            async move { post(&url, b"{}", &signature).await }
+        });
+        assert_eq!(res, (Some(StatusCode::OK), String::new(), false));
+        let (headers, body) = server.join().unwrap();
+        assert_eq!(body, b"{}");
+        assert!(headers.contains(&"content-type: application/json".into()));
+        assert!(headers.contains(&format!(
+            "{}: {}",
+            SIGNATURE_HEADER.to_lowercase(),
+            signature
+        )));
+    }
+
+    #[test]
+    fn post_retries_only_server_errors() {
+        for (status, code, retry) in [
+            (
+                "500 Internal Server Error",
+                StatusCode::INTERNAL_SERVER_ERROR,
+                true,
+            ),
+            ("429 Too Many Requests", StatusCode::TOO_MANY_REQUESTS, true),
+            ("404 Not Found", StatusCode::NOT_FOUND, false),
+        ] {
+            let (url, server) = answer_once(status);
+            let (answer, error, retried) = actix_web::rt::System::new("test")
+                .block_on(async move { post(&url, b"{}", "sha256=0").await });
+            server.join().unwrap();
+            assert_eq!(answer, Some(code));
+            assert_eq!(error, format!("Answered with {}", code));
+            assert_eq!(retried, retry);
+        }
+    }
+}
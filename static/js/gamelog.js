@@ -26,6 +26,11 @@ document.getElementById("chatonly").addEventListener('change', function () {
     }
 })
 
+// Report the event whose button opened the form
+document.getElementById("report").addEventListener('show.bs.modal', function (event) {
+    document.getElementById("report-event").value = event.relatedTarget.dataset.event
+})
+
 function setVisible(selector, visible) {
     document.querySelectorAll(selector).forEach(e => e.setAttribute("style", `display: ${visible ? "inherit" : "none !important"}`))
 }
\ No newline at end of file